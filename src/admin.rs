@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::http_client::HttpClientBuilder;
+use jsonrpsee::rpc_params;
+use jsonrpsee::server::{RpcModule, ServerBuilder, ServerHandle};
+
+use crate::rpc::MiningContext;
+use crate::stats::StatsSnapshot;
+
+/// Serves local administration methods, keep it bound to loopback.
+pub(crate) async fn start_admin_server(ctx: Arc<MiningContext>, addr: SocketAddr) -> anyhow::Result<ServerHandle> {
+    let server = ServerBuilder::default().build(addr).await?;
+
+    let mut module = RpcModule::new(ctx);
+    module.register_method("pool_stats", |_, ctx| Ok(ctx.stats_snapshot()))?;
+
+    let handle = server.start(module)?;
+    println!("🛠️  Admin RPC listening on {}", addr);
+    Ok(handle)
+}
+
+/// Fetches stats from the admin RPC of a running instance
+pub(crate) async fn fetch_stats(url: &str) -> anyhow::Result<StatsSnapshot> {
+    let client = HttpClientBuilder::default().build(url)?;
+    let stats: StatsSnapshot = client.request("pool_stats", rpc_params![]).await?;
+    Ok(stats)
+}
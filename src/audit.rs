@@ -0,0 +1,189 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use codec::Encode;
+use primitive_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+/// One submission in the audit log. `hash` covers every other field including
+/// `prev`, the hash of the record before it, so editing, dropping or reordering
+/// records breaks the chain from there on.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AuditRecord {
+    pub(crate) seq: u64,
+    /// Unix time in seconds
+    pub(crate) time: u64,
+    pub(crate) obj_id: u64,
+    pub(crate) obj_hash: H256,
+    pub(crate) difficulty: U256,
+    pub(crate) outcome: String,
+    /// Zero for the first record
+    pub(crate) prev: H256,
+    pub(crate) hash: H256,
+}
+
+impl AuditRecord {
+    fn calc_hash(&self) -> H256 {
+        let data = (self.seq, self.time, self.obj_id, self.obj_hash, self.difficulty, &self.outcome, self.prev).encode();
+        H256::from_slice(Sha3_256::digest(&data[..]).as_slice())
+    }
+}
+
+struct Chain {
+    file: File,
+    seq: u64,
+    prev: H256,
+}
+
+/// Append-only, hash chained log of submissions. Holds no key material.
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    chain: Mutex<Chain>,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending, continuing the chain of an existing log
+    pub(crate) fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let (seq, prev) = match File::open(&path) {
+            Ok(_) => verify(&path)?,
+            Err(_) => (0, H256::zero()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            chain: Mutex::new(Chain { file, seq, prev }),
+        })
+    }
+
+    pub(crate) fn record(&self, obj_id: u64, obj_hash: H256, difficulty: U256, outcome: &str) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        let mut chain = self.chain.lock().unwrap();
+        let mut record = AuditRecord {
+            seq: chain.seq + 1,
+            time,
+            obj_id,
+            obj_hash,
+            difficulty,
+            outcome: outcome.to_string(),
+            prev: chain.prev,
+            hash: H256::zero(),
+        };
+        record.hash = record.calc_hash();
+
+        let mut line = serde_json::to_vec(&record).unwrap();
+        line.push(b'\n');
+        // Only advance on success, a record missing from the file mustn't be chained to
+        match chain.file.write_all(&line).and_then(|_| chain.file.flush()) {
+            Ok(_) => {
+                chain.seq = record.seq;
+                chain.prev = record.hash;
+            }
+            Err(e) => println!("🟥 Audit log {} write error: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Checks the chain of the log at `path`, returns the last seq and hash
+pub(crate) fn verify(path: &Path) -> anyhow::Result<(u64, H256)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut seq = 0;
+    let mut prev = H256::zero();
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let line_no = idx + 1;
+        let record: AuditRecord = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("Line {}: malformed record: {}", line_no, e))?;
+        if record.seq != seq + 1 {
+            anyhow::bail!("Line {}: expected seq {}, found {}", line_no, seq + 1, record.seq);
+        }
+        if record.prev != prev {
+            anyhow::bail!("Line {}: doesn't chain to the previous record", line_no);
+        }
+        if record.hash != record.calc_hash() {
+            anyhow::bail!("Line {}: record was modified", line_no);
+        }
+        seq = record.seq;
+        prev = record.hash;
+    }
+    Ok((seq, prev))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh path in the temp dir, removed when dropped
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("pass3d-pool-{}-{}.log", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn lines(&self) -> Vec<String> {
+            std::fs::read_to_string(&self.0).unwrap().lines().map(String::from).collect()
+        }
+
+        fn write_lines(&self, lines: &[String]) {
+            std::fs::write(&self.0, lines.join("\n") + "\n").unwrap();
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_records(log: &TempLog, count: u64) {
+        let audit_log = AuditLog::open(log.0.clone()).unwrap();
+        for obj_id in 0..count {
+            audit_log.record(obj_id, H256::repeat_byte(obj_id as u8), U256::from(10), "submitted");
+        }
+    }
+
+    #[test]
+    fn intact_chain_verifies_and_continues() {
+        let log = TempLog::new("intact");
+        write_records(&log, 2);
+        write_records(&log, 1);
+
+        let (seq, last_hash) = verify(&log.0).unwrap();
+        assert_eq!(seq, 3);
+        let last: AuditRecord = serde_json::from_str(&log.lines()[2]).unwrap();
+        assert_eq!(last_hash, last.hash);
+    }
+
+    #[test]
+    fn tampered_chain_fails() {
+        let log = TempLog::new("tampered");
+        write_records(&log, 3);
+        let lines = log.lines();
+
+        let mut edited = lines.clone();
+        edited[1] = edited[1].replace("submitted", "rejected");
+        log.write_lines(&edited);
+        assert!(verify(&log.0).unwrap_err().to_string().contains("Line 2: record was modified"));
+
+        log.write_lines(&[lines[0].clone(), lines[2].clone()]);
+        assert!(verify(&log.0).unwrap_err().to_string().contains("Line 2: expected seq 2"));
+
+        log.write_lines(&[lines[1].clone(), lines[0].clone(), lines[2].clone()]);
+        assert!(verify(&log.0).is_err());
+
+        // Appending to a broken log would chain to a record that can't be trusted
+        assert!(AuditLog::open(log.0.clone()).is_err());
+    }
+}
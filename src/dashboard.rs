@@ -0,0 +1,117 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::rpc::{MiningContext, SubmissionRecord};
+use crate::stats::StatsSnapshot;
+
+const PAGE: &str = include_str!("dashboard.html");
+/// Requests are just a request line and a few headers
+const MAX_REQUEST_SIZE: usize = 4096;
+
+#[derive(Serialize)]
+struct Status {
+    stats: StatsSnapshot,
+    submissions: Vec<SubmissionRecord>,
+}
+
+/// Serves a read only HTML status page on `/` and its data on `/status`.
+/// Like the admin RPC it's unauthenticated, keep it bound to loopback or a trusted network.
+pub(crate) async fn start_dashboard(ctx: Arc<MiningContext>, addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("📊 Dashboard on http://{}", addr);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let ctx = ctx.clone();
+                    tokio::spawn(async move {
+                        // Browsers going away mid request aren't worth reporting
+                        let _ = serve(&ctx, stream).await;
+                    });
+                }
+                Err(e) => println!("🟥 Dashboard accept error: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn serve(ctx: &MiningContext, mut stream: TcpStream) -> anyhow::Result<()> {
+    let mut buf = vec![0; MAX_REQUEST_SIZE];
+    let mut len = 0;
+    while !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+        if len == buf.len() {
+            anyhow::bail!("Request too large");
+        }
+        match stream.read(&mut buf[len..]).await? {
+            0 => anyhow::bail!("Connection closed"),
+            n => len += n,
+        }
+    }
+
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/")) => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+        (Some("GET"), Some("/status")) => {
+            let status = Status {
+                stats: ctx.stats_snapshot(),
+                submissions: ctx.submission_history.lock().unwrap().iter().cloned().collect(),
+            };
+            ("200 OK", "application/json", serde_json::to_string(&status)?)
+        }
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Method not allowed".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::tests::{plaintext, test_context, MEMBER_KEY};
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_the_page_and_its_data() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let ctx = Arc::new(test_context("http://127.0.0.1:1", plaintext()));
+        start_dashboard(ctx, addr).await.unwrap();
+
+        let page = get(addr, "/").await;
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{}", page);
+        assert!(page.contains("Content-Type: text/html"));
+        let html = page.split("\r\n\r\n").nth(1).unwrap();
+        assert!(html.trim_start().starts_with("<!DOCTYPE html>") && html.trim_end().ends_with("</html>"));
+
+        let status = get(addr, "/status").await;
+        assert!(status.starts_with("HTTP/1.1 200 OK\r\n"), "{}", status);
+        let status: serde_json::Value = serde_json::from_str(status.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert!(status["stats"].is_object());
+        assert!(!status.to_string().contains(&MEMBER_KEY[2..]));
+
+        assert!(get(addr, "/missing").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
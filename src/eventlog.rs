@@ -0,0 +1,217 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use primitive_types::{H256, U256};
+use serde::Serialize;
+
+/// Rotated files kept next to the current one: `<path>.1` (newest) .. `<path>.<KEEP>`
+const KEEP: usize = 3;
+
+/// Pipeline step recorded in the event log. Only ids, hashes and sizes are
+/// logged, never key material or object bytes.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum PipelineEvent {
+    ObjectReceived { obj_id: u64, size: usize },
+    ComputeStarted { obj_id: u64, pre_hash: H256 },
+    ComputeFinished { obj_id: u64, obj_hash: Option<H256> },
+    ProposalFound { obj_id: u64, obj_hash: H256, pre_hash: H256, difficulty: U256 },
+    SubmissionOutcome { obj_hash: H256, outcome: String },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    seq: u64,
+    ts_ms: u64,
+    #[serde(flatten)]
+    event: &'a PipelineEvent,
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+    seq: u64,
+}
+
+/// Append-only JSON lines log of the mining pipeline, rotated by size.
+pub(crate) struct EventLog {
+    path: PathBuf,
+    max_size: u64,
+    state: Mutex<LogFile>,
+}
+
+impl EventLog {
+    /// Opens `path` for appending, numbering on from the last record of an existing log
+    pub(crate) fn open(path: PathBuf, max_size: u64) -> io::Result<Self> {
+        // Right after a rotation the current file is still empty
+        let seq = last_seq(&path).or_else(|| last_seq(&rotated_path(&path, 1))).unwrap_or(0);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size,
+            state: Mutex::new(LogFile { file, size, seq }),
+        })
+    }
+
+    pub(crate) fn record(&self, event: &PipelineEvent) {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut state = self.state.lock().unwrap();
+        state.seq += 1;
+        let record = Record { seq: state.seq, ts_ms, event };
+        let mut line = serde_json::to_vec(&record).unwrap();
+        line.push(b'\n');
+
+        if state.size + line.len() as u64 > self.max_size {
+            if let Err(e) = self.rotate(&mut state) {
+                println!("🟥 Event log rotation error: {}", e);
+            }
+        }
+        match state.file.write_all(&line) {
+            Ok(_) => state.size += line.len() as u64,
+            Err(e) => println!("🟥 Event log write error: {}", e),
+        }
+    }
+
+    fn rotate(&self, state: &mut LogFile) -> io::Result<()> {
+        let rotated = |i: usize| rotated_path(&self.path, i);
+        for i in (1..KEEP).rev() {
+            if rotated(i).exists() {
+                fs::rename(rotated(i), rotated(i + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))?;
+
+        state.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        state.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, i: usize) -> PathBuf {
+    let mut path = path.to_path_buf().into_os_string();
+    path.push(format!(".{}", i));
+    PathBuf::from(path)
+}
+
+/// Seq of the last record in the log file at `path`, None without one
+fn last_seq(path: &Path) -> Option<u64> {
+    let contents = fs::read(path).ok()?;
+    contents
+        .split(|&b| b == b'\n')
+        .rev()
+        .find_map(|line| serde_json::from_slice::<serde_json::Value>(line).ok()?.get("seq")?.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Event log path in the temp dir, the log and its rotations are removed when dropped
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let log = Self(std::env::temp_dir().join(format!("pass3d-pool-events-{}-{}.log", name, std::process::id())));
+            log.remove();
+            log
+        }
+
+        fn rotated(&self, i: usize) -> PathBuf {
+            PathBuf::from(format!("{}.{}", self.0.display(), i))
+        }
+
+        fn remove(&self) {
+            let _ = fs::remove_file(&self.0);
+            for i in 1..=KEEP + 1 {
+                let _ = fs::remove_file(self.rotated(i));
+            }
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    #[test]
+    fn events_are_written_in_order() {
+        let log = TempLog::new("order");
+        let event_log = EventLog::open(log.0.clone(), u64::MAX).unwrap();
+        let obj_hash = H256::repeat_byte(2);
+        event_log.record(&PipelineEvent::ObjectReceived { obj_id: 7, size: 100 });
+        event_log.record(&PipelineEvent::ComputeStarted { obj_id: 7, pre_hash: H256::repeat_byte(1) });
+        event_log.record(&PipelineEvent::ComputeFinished { obj_id: 7, obj_hash: Some(obj_hash) });
+        event_log.record(&PipelineEvent::ProposalFound {
+            obj_id: 7,
+            obj_hash,
+            pre_hash: H256::repeat_byte(1),
+            difficulty: U256::from(10),
+        });
+        event_log.record(&PipelineEvent::SubmissionOutcome { obj_hash, outcome: "submitted".into() });
+
+        let records: Vec<serde_json::Value> = fs::read_to_string(&log.0)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<&str> = records.iter().map(|record| record["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["object_received", "compute_started", "compute_finished", "proposal_found", "submission_outcome"]);
+        let seqs: Vec<u64> = records.iter().map(|record| record["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, [1, 2, 3, 4, 5]);
+        assert!(records.windows(2).all(|pair| pair[0]["ts_ms"].as_u64() <= pair[1]["ts_ms"].as_u64()));
+        assert_eq!(records[0]["obj_id"], 7);
+    }
+
+    #[test]
+    fn rotation_keeps_the_last_files() {
+        let log = TempLog::new("rotation");
+        // Every record is over the limit, so each one starts a new file
+        let event_log = EventLog::open(log.0.clone(), 10).unwrap();
+        for obj_id in 0..6 {
+            event_log.record(&PipelineEvent::ObjectReceived { obj_id, size: 1 });
+        }
+
+        assert!(log.0.exists());
+        for i in 1..=KEEP {
+            assert!(log.rotated(i).exists(), "no {}", log.rotated(i).display());
+        }
+        assert!(!log.rotated(KEEP + 1).exists());
+        // The newest record in the current file, the one before it in .1
+        assert!(fs::read_to_string(&log.0).unwrap().contains("\"obj_id\":5"));
+        assert!(fs::read_to_string(log.rotated(1)).unwrap().contains("\"obj_id\":4"));
+    }
+
+    #[test]
+    fn reopened_logs_number_on() {
+        let log = TempLog::new("reopen");
+        let seqs = |path: &Path| -> Vec<u64> {
+            fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["seq"].as_u64().unwrap())
+                .collect()
+        };
+        for _ in 0..2 {
+            let event_log = EventLog::open(log.0.clone(), u64::MAX).unwrap();
+            event_log.record(&PipelineEvent::ObjectReceived { obj_id: 1, size: 1 });
+            event_log.record(&PipelineEvent::ObjectReceived { obj_id: 2, size: 1 });
+        }
+        assert_eq!(seqs(&log.0), [1, 2, 3, 4]);
+
+        // Stopped right after a rotation, before anything went to the new file
+        fs::rename(&log.0, log.rotated(1)).unwrap();
+        let event_log = EventLog::open(log.0.clone(), u64::MAX).unwrap();
+        event_log.record(&PipelineEvent::ObjectReceived { obj_id: 3, size: 1 });
+        assert_eq!(seqs(&log.0), [5]);
+    }
+}
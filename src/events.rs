@@ -0,0 +1,45 @@
+use std::fmt;
+
+use primitive_types::U256;
+
+use crate::worker::format_difficulty;
+
+/// Connection state of the pool node. There's no `EndpointSwitched`: the miner polls a
+/// single `--url` and has no failover list to switch between
+#[derive(Clone, Debug)]
+pub(crate) enum ConnectionEvent {
+    Connected,
+    Disconnected { reason: String },
+    Reconnecting { attempt: usize },
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected => write!(f, "Connected to pool node"),
+            Self::Disconnected { reason } => write!(f, "Disconnected from pool node: {}", reason),
+            Self::Reconnecting { attempt } => write!(f, "Reconnecting to pool node (attempt {})", attempt),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ParamsEvent {
+    /// New params came with a different pool or network difficulty
+    DifficultyChanged { pow_from: U256, pow_to: U256, win_from: U256, win_to: U256 },
+}
+
+impl fmt::Display for ParamsEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DifficultyChanged { pow_from, pow_to, win_from, win_to } => write!(
+                f,
+                "Difficulty changed: pool {} -> {}, network {} -> {}",
+                format_difficulty(pow_from),
+                format_difficulty(pow_to),
+                format_difficulty(win_from),
+                format_difficulty(win_to),
+            ),
+        }
+    }
+}
@@ -1,15 +1,33 @@
 #![feature(async_closure)]
 
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 
 use bip39::{Language, Mnemonic};
+use primitive_types::U256;
 use structopt::StructOpt;
 use substrate_bip39::mini_secret_from_entropy;
 
-use crate::rpc::{MiningContext, P3dParams};
+use crate::audit::AuditLog;
+use crate::eventlog::EventLog;
+use crate::rpc::{EntropySource, MiningContext, MiningOptions, ObjEncoding, ObjTransform, ObjectFilter, P3dParams, SubmitOrder, WireFormat};
+use crate::signer::{KeySigner, RemoteSigner, Signer};
 
+mod admin;
+mod audit;
+mod dashboard;
+mod eventlog;
+mod events;
+mod metrics;
+mod ratelimit;
 mod rpc;
+mod signer;
+mod source;
+mod stats;
 mod worker;
 
 #[derive(Debug, StructOpt)]
@@ -18,6 +36,14 @@ enum SubCommand {
     Run(RunOptions),
     #[structopt(name = "inspect", about = "Use inspect to convert seed to key")]
     Inspect(InspectOptions),
+    #[structopt(name = "bench", about = "Use bench to measure mining speed without a pool")]
+    Bench(BenchOptions),
+    #[structopt(name = "selftest", about = "Use selftest to check mining, encryption and signing work on this host")]
+    Selftest(SelftestOptions),
+    #[structopt(name = "stats", about = "Use stats to print statistics of a running instance")]
+    Stats(StatsOptions),
+    #[structopt(name = "verify-audit", about = "Use verify-audit to check an audit log wasn't tampered with")]
+    VerifyAudit(VerifyAuditOptions),
 }
 
 #[derive(Debug, StructOpt)]
@@ -31,10 +57,86 @@ struct RunOptions {
     /// Number of threads
     threads: Option<u16>,
 
+    #[structopt(long)]
+    /// Grow the number of threads up to this while objects from the pool node queue up
+    max_threads: Option<usize>,
+
     #[structopt(default_value = "http://127.0.0.1:9933", short, long)]
     /// Pool url
     url: String,
 
+    #[structopt(long)]
+    /// Pool node url to push found objects to, if not the one at --url
+    submit_url: Option<String>,
+
+    #[structopt(long)]
+    /// Pool node url to also push every submission to, logging where its verdict differs
+    shadow_url: Option<String>,
+
+    #[structopt(long)]
+    /// Chain node url to check the pool node isn't lagging behind
+    reference_node: Option<String>,
+
+    #[structopt(long)]
+    /// Check the configuration for common mistakes and refuse to start if any is found
+    safe: bool,
+
+    #[structopt(long)]
+    /// Switch to the next algorithm after this many rejections in a row, until one is accepted
+    algo_fallback: Option<usize>,
+
+    #[structopt(long)]
+    /// Max submissions per second, to stay within the pool's limits
+    max_submit_rate: Option<f64>,
+
+    #[structopt(long)]
+    /// Push a found object once more if the connection dropped while pushing it
+    resubmit_unknown: bool,
+
+    #[structopt(long)]
+    /// Only submit objects meeting the network difficulty, for pools not paying for shares
+    blocks_only: bool,
+
+    #[structopt(long)]
+    /// Print a one line status (hashrate, accepted, rejected, queue, uptime) every this many seconds
+    status_interval: Option<u64>,
+
+    #[structopt(default_value = "fifo", long)]
+    /// Order queued proposals are submitted in: fifo or difficulty (highest first)
+    submit_order: SubmitOrder,
+
+    #[structopt(default_value = "60", long)]
+    /// Seconds the queues have to stay mostly empty before their spare memory is released
+    compact_after: u64,
+
+    #[structopt(default_value = "1", long)]
+    /// Max objects a worker takes from the queue at once, fewer lock round trips with many threads
+    prefetch: usize,
+
+    #[structopt(long, parse(from_os_str))]
+    /// File created once the miner is up and has mining params, removed on shutdown
+    ready_file: Option<PathBuf>,
+
+    #[structopt(default_value = "0", long)]
+    /// Seconds to hold found objects after the first mining params before submitting
+    warmup: u64,
+
+    #[structopt(long, parse(try_from_str = parse_id_range))]
+    /// Only mine node objects with an obj_id in this range, e.g. 1000-1999
+    obj_ids: Option<RangeInclusive<u64>>,
+
+    #[structopt(long)]
+    /// Only mine node objects of at least this many bytes
+    min_obj_size: Option<usize>,
+
+    #[structopt(long)]
+    /// Only mine node objects of at most this many bytes
+    max_obj_size: Option<usize>,
+
+    #[structopt(long)]
+    /// Rewrite node objects before mining: strip-comments, normalize-newlines or trim-lines, repeatable
+    obj_transform: Vec<ObjTransform>,
+
     #[structopt(short, long)]
     /// Pool AccountId
     pool_id: String,
@@ -43,9 +145,203 @@ struct RunOptions {
     /// Pool member AccountId
     member_id: String,
 
-    #[structopt(short, long)]
-    /// Member key to sign requests
-    key: String,
+    #[structopt(long)]
+    /// Name of this rig, sent with submissions for per-rig stats in the pool
+    rig_id: Option<String>,
+
+    #[structopt(short, long, required_unless = "remote-signer")]
+    /// Member key to sign requests, hex or a mnemonic phrase
+    key: Option<String>,
+
+    #[structopt(long, requires = "key")]
+    /// Password of mnemonic phrase keys
+    key_password: Option<String>,
+
+    #[structopt(long, conflicts_with = "key")]
+    /// Address (host:port) of a remote signer to use instead of the member key
+    remote_signer: Option<String>,
+
+    #[structopt(long)]
+    /// Keys to rotate to on SIGUSR1, in order, may be repeated
+    next_key: Vec<String>,
+
+    #[structopt(default_value = "4", long)]
+    /// Max number of submissions pushed to the pool node concurrently
+    max_inflight: usize,
+
+    #[structopt(long)]
+    /// Submit plaintext payloads, only for pools that don't use encryption
+    no_encryption: bool,
+
+    #[structopt(default_value = "10", long)]
+    /// Seconds to keep pushing queued objects to the pool node on shutdown before dropping them
+    shutdown_drain_timeout: u64,
+
+    #[structopt(default_value = "60", long)]
+    /// Seconds during which an identical submission isn't pushed again, 0 disables
+    dedup_window: u64,
+
+    #[structopt(default_value = "300", long)]
+    /// Seconds without progress after which a worker thread is considered stuck and replaced
+    worker_stall_timeout: u64,
+
+    #[structopt(long)]
+    /// Memory budget in MB for queued objects, found proposals and caches, oldest entries are dropped above it
+    memory_budget: Option<usize>,
+
+    #[structopt(long)]
+    /// Max objects in flight across the pipeline (queued, being mined, waiting for submission)
+    max_pipeline_objects: Option<usize>,
+
+    #[structopt(default_value = "os", long)]
+    /// RNG for payload encryption: os, thread or seeded:<n> (reproducible, testing only)
+    entropy: EntropySource,
+
+    #[structopt(default_value = "json", long)]
+    /// Payload encoding expected by the pool node: json or scale
+    wire_format: WireFormat,
+
+    #[structopt(default_value = "array", long)]
+    /// Encoding of the object in json payloads expected by the pool node: array, hex or base64
+    obj_encoding: ObjEncoding,
+
+    #[structopt(long)]
+    /// Seconds to hold found objects for and push them together, instead of as soon as found
+    drain_interval: Option<u64>,
+
+    #[structopt(long, requires = "drain-interval")]
+    /// Push held objects early once this many are queued
+    drain_threshold: Option<usize>,
+
+    #[structopt(long)]
+    /// Compress the payload with zstd before encryption, the pool node has to support it
+    compress: bool,
+
+    #[structopt(long, parse(try_from_str = parse_difficulty))]
+    /// Min difficulty (decimal) of submitted objects, for skipping low value ones. Clamped to the network difficulty
+    min_difficulty: Option<U256>,
+
+    #[structopt(long, parse(try_from_str = parse_difficulty))]
+    /// Compute objects found at this difficulty (decimal) or above a second time before submitting
+    double_check_difficulty: Option<U256>,
+
+    #[structopt(long)]
+    /// Address to serve the admin RPC (stats etc.) on, e.g. 127.0.0.1:9834. Disabled by default
+    admin_addr: Option<SocketAddr>,
+
+    #[structopt(long)]
+    /// Address to serve the HTML status dashboard on, e.g. 127.0.0.1:9835. Disabled by default
+    dashboard_addr: Option<SocketAddr>,
+
+    #[structopt(default_value = "0", long)]
+    /// Times to mine an object again when p3d fails on it before dropping it
+    compute_retries: usize,
+
+    #[structopt(long, parse(from_os_str))]
+    /// File to append the pipeline event log to (JSON lines), for debugging
+    event_log: Option<PathBuf>,
+
+    #[structopt(default_value = "10", long)]
+    /// Size in MB after which the event log is rotated
+    event_log_max_size: u64,
+
+    #[structopt(long, parse(from_os_str))]
+    /// File to append a tamper evident record of every submission to
+    audit_log: Option<PathBuf>,
+
+    #[structopt(long)]
+    /// Print the payload of rejected submissions for debugging, object data left out
+    log_rejected: bool,
+
+    #[structopt(long, requires = "log-rejected")]
+    /// Include the object data in logged rejected payloads (large, and reveals the mined object)
+    log_rejected_obj: bool,
+}
+
+/// Settings that are accepted but can't work, checked with `--safe`
+async fn config_problems(opt: &RunOptions) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if opt.member_id.trim().is_empty() {
+        problems.push("--member-id is empty".to_string());
+    }
+    if opt.pool_id.trim().is_empty() {
+        problems.push("--pool-id is empty".to_string());
+    }
+    for key in opt.key.iter().chain(opt.next_key.iter()) {
+        if let Ok(key) = hex::decode(key.replacen("0x", "", 1)) {
+            if key.iter().all(|&b| b == 0) {
+                problems.push("A member key is all zeros".to_string());
+            }
+        }
+    }
+    if let Ok(pool_addrs) = rpc::resolve_addrs(&opt.url).await {
+        let own_addrs = [("--admin-addr", opt.admin_addr), ("--dashboard-addr", opt.dashboard_addr)];
+        for (name, addr) in own_addrs {
+            if let Some(addr) = addr.filter(|addr| pool_addrs.iter().any(|pool_addr| reaches_own_addr(addr, pool_addr))) {
+                problems.push(format!("--url points at this miner's own {} {}", name, addr));
+            }
+        }
+    }
+    // No algo/sect check: the sect is derived from --algo, there's no setting for it to disagree with
+    if let Some(ids) = &opt.obj_ids {
+        if ids.is_empty() {
+            problems.push(format!("--obj-ids {}-{} matches no object", ids.start(), ids.end()));
+        }
+    }
+    if let (Some(min), Some(max)) = (opt.min_obj_size, opt.max_obj_size) {
+        if min > max {
+            problems.push(format!("--min-obj-size {} is above --max-obj-size {}", min, max));
+        }
+    }
+    if opt.max_threads.is_some_and(|max| max < opt.threads.unwrap_or(1) as usize) {
+        problems.push("--max-threads is below --threads".to_string());
+    }
+    problems
+}
+
+/// Whether connecting to `pool` reaches a server listening on `own`. Loopback listeners
+/// are reached through any loopback address, unspecified ones through any local address.
+fn reaches_own_addr(own: &SocketAddr, pool: &SocketAddr) -> bool {
+    if own.port() != pool.port() {
+        return false;
+    }
+    let (own, pool) = (own.ip(), pool.ip());
+    own == pool || (own.is_loopback() && pool.is_loopback()) || (own.is_unspecified() && is_local_ip(pool))
+}
+
+/// Loopback, unspecified or the address of one of this host's interfaces, only those can be bound
+fn is_local_ip(ip: IpAddr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || std::net::UdpSocket::bind((ip, 0)).is_ok()
+}
+
+/// With `--safe` any of the `config_problems` refuses the start, without it they're ignored
+async fn check_config(opt: &RunOptions) -> Result<(), ConfigError> {
+    if !opt.safe {
+        return Ok(());
+    }
+    let problems = config_problems(opt).await;
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for problem in problems.iter() {
+        println!("🟥 {}", problem);
+    }
+    Err(ConfigError(format!(
+        "Refusing to start with {} configuration problems, fix them or drop --safe",
+        problems.len(),
+    )))
+}
+
+fn parse_id_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let (start, end) = s.split_once('-').ok_or_else(|| format!("Invalid range {}, expected <from>-<to>", s))?;
+    let start = start.trim().parse::<u64>().map_err(|e| format!("Invalid range start {}: {}", start, e))?;
+    let end = end.trim().parse::<u64>().map_err(|e| format!("Invalid range end {}: {}", end, e))?;
+    Ok(start..=end)
+}
+
+fn parse_difficulty(s: &str) -> Result<U256, String> {
+    U256::from_dec_str(s).map_err(|e| format!("Invalid difficulty {}: {:?}", s, e))
 }
 
 #[derive(Debug, StructOpt)]
@@ -55,16 +351,97 @@ struct InspectOptions {
     seed: String,
 }
 
+#[derive(Debug, StructOpt)]
+struct BenchOptions {
+    #[structopt(default_value = "grid2d_v3.1", short, long)]
+    /// Mining algorithm. Supported algorithms: grid2d, grid2d_v2, grid2d_v3, grid2d_v3.1
+    algo: String,
+
+    #[structopt(short, long)]
+    /// Number of threads
+    threads: Option<u16>,
+
+    #[structopt(default_value = "60", short, long)]
+    /// Benchmark duration in seconds
+    duration: u64,
+
+    #[structopt(long)]
+    /// Seed for reproducible object generation, for benchmark comparisons only
+    seed: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+struct StatsOptions {
+    #[structopt(default_value = "http://127.0.0.1:9834", short, long)]
+    /// Admin RPC url of the running instance
+    url: String,
+
+    #[structopt(long)]
+    /// Print the raw JSON snapshot
+    json: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct SelftestOptions {
+    #[structopt(default_value = "grid2d_v3.1", short, long)]
+    /// Mining algorithm. Supported algorithms: grid2d, grid2d_v2, grid2d_v3, grid2d_v3.1
+    algo: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct VerifyAuditOptions {
+    #[structopt(parse(from_os_str))]
+    /// Audit log written with --audit-log
+    file: PathBuf,
+}
+
 #[derive(StructOpt)]
 struct Cli {
     #[structopt(subcommand)]
     cmd: SubCommand,
 }
 
+/// Error in the command line rather than at runtime, exits with code 2
+#[derive(Debug)]
+struct ConfigError(String);
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Exit codes: 0 on clean shutdown, 2 on configuration errors, 1 on anything else
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Cli::from_args();
+async fn main() -> ExitCode {
+    let args = match Cli::from_iter_safe(std::env::args_os()) {
+        Ok(args) => args,
+        // --help and --version end up here too
+        Err(e) if !e.use_stderr() => {
+            println!("{}", e.message);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", e.message);
+            return ExitCode::from(2);
+        }
+    };
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            if e.is::<ConfigError>() {
+                ExitCode::from(2)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
 
+async fn run(args: Cli) -> anyhow::Result<()> {
     match args.cmd {
         SubCommand::Inspect(opt) => {
             let mnemonic = Mnemonic::from_phrase(&opt.seed, Language::English);
@@ -78,20 +455,321 @@ async fn main() -> anyhow::Result<()> {
             };
             Ok(())
         }
+        SubCommand::Bench(opt) => {
+            let p3d_params = p3d_params(&opt.algo)?;
+            worker::bench(
+                p3d_params,
+                opt.threads.unwrap_or(1) as usize,
+                Duration::from_secs(opt.duration),
+                opt.seed,
+            );
+            Ok(())
+        }
+        SubCommand::Stats(opt) => {
+            let stats = admin::fetch_stats(&opt.url).await?;
+            if opt.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else if let serde_json::Value::Object(fields) = serde_json::to_value(&stats)? {
+                for (name, value) in fields {
+                    println!("{}: {}", name, value);
+                }
+            }
+            Ok(())
+        }
+        SubCommand::Selftest(opt) => {
+            worker::selftest(p3d_params(&opt.algo)?)?;
+            println!("✅ Selftest passed");
+            Ok(())
+        }
+        SubCommand::VerifyAudit(opt) => {
+            let (records, last_hash) = audit::verify(&opt.file)?;
+            println!("✅ Audit log intact: {} records, last hash {:?}", records, last_hash);
+            Ok(())
+        }
         SubCommand::Run(opt) => {
-            let p3d_params = P3dParams::new(opt.algo.as_str());
-            let ctx = MiningContext::new(p3d_params, opt.url.as_str(), opt.pool_id, opt.member_id, opt.key)?;
+            check_config(&opt).await?;
+            if opt.max_submit_rate.is_some_and(|rate| rate <= 0.0) {
+                return Err(ConfigError("--max-submit-rate has to be above 0".into()).into());
+            }
+            rpc::report_addrs("Pool node", &opt.url).await;
+            let nodes = [("Submission node", &opt.submit_url), ("Shadow node", &opt.shadow_url)];
+            for (node, url) in nodes {
+                if let Some(url) = url.as_ref().filter(|&url| *url != opt.url) {
+                    rpc::report_addrs(node, url).await;
+                }
+            }
+            let p3d_params = p3d_params(&opt.algo)?;
+            let options = MiningOptions {
+                max_inflight: opt.max_inflight,
+                no_encryption: opt.no_encryption,
+                dedup_window: Duration::from_secs(opt.dedup_window),
+                memory_budget: opt.memory_budget.map(|mb| mb * 1_000_000),
+                max_pipeline_objects: opt.max_pipeline_objects,
+                wire_format: opt.wire_format,
+                obj_encoding: opt.obj_encoding,
+                drain_interval: opt.drain_interval.map(Duration::from_secs),
+                drain_threshold: opt.drain_threshold,
+                compress: opt.compress,
+                min_difficulty: opt.min_difficulty,
+                double_check_difficulty: opt.double_check_difficulty,
+                event_log: match opt.event_log {
+                    Some(path) => Some(EventLog::open(path, opt.event_log_max_size * 1_000_000)?),
+                    None => None,
+                },
+                rig_id: opt.rig_id,
+                compute_retries: opt.compute_retries,
+                entropy: opt.entropy,
+                audit_log: match opt.audit_log {
+                    Some(path) => Some(AuditLog::open(path)?),
+                    None => None,
+                },
+                log_rejected: opt.log_rejected,
+                log_rejected_obj: opt.log_rejected_obj,
+                submit_addr: opt.submit_url,
+                reference_node: opt.reference_node,
+                shadow_addr: opt.shadow_url,
+                warmup: Duration::from_secs(opt.warmup),
+                object_filter: ObjectFilter {
+                    obj_ids: opt.obj_ids,
+                    min_size: opt.min_obj_size,
+                    max_size: opt.max_obj_size,
+                },
+                algo_fallback: opt.algo_fallback,
+                max_submit_rate: opt.max_submit_rate,
+                resubmit_unknown: opt.resubmit_unknown,
+                blocks_only: opt.blocks_only,
+                prefetch: opt.prefetch,
+                compact_after: Duration::from_secs(opt.compact_after),
+                submit_order: opt.submit_order,
+                obj_transforms: opt.obj_transform,
+            };
+            if options.rig_id.is_some() && options.wire_format == WireFormat::Scale {
+                println!("⚠️  --rig-id is only sent with the json wire format");
+            }
+            if options.entropy != EntropySource::Os && !opt.no_encryption {
+                println!("⚠️  Payload encryption uses {:?} entropy instead of the OS RNG", options.entropy);
+            }
+            if opt.no_encryption {
+                println!("⚠️  Encryption is disabled: objects are submitted to the pool node UNENCRYPTED");
+            }
+            let key_password = opt.key_password.as_deref().unwrap_or("");
+            let signer: Box<dyn Signer> = match (opt.key, opt.remote_signer) {
+                (_, Some(addr)) => Box::new(RemoteSigner::new(addr)),
+                (Some(key), None) => Box::new(load_key(&key, "--key", key_password)?),
+                (None, None) => return Err(ConfigError("Either --key or --remote-signer is required".into()).into()),
+            };
+            let mut signers = vec![signer];
+            for key in opt.next_key.iter() {
+                signers.push(Box::new(load_key(key, "--next-key", key_password)?));
+            }
+            let ctx = MiningContext::new(p3d_params, opt.url.as_str(), opt.pool_id, opt.member_id, signers, options)
+                .map_err(|e| ConfigError(format!("{:#}", e)))?;
+            ctx.negotiate_capabilities().await;
             let ctx = Arc::new(ctx);
             tokio::spawn(worker::node_client(ctx.clone()));
+            tokio::spawn(worker::log_connection_events(ctx.clone()));
+            tokio::spawn(worker::log_params_events(ctx.clone()));
+            #[cfg(unix)]
+            tokio::spawn(rotate_key_on_signal(ctx.clone()));
 
-            for _ in 0..opt.threads.unwrap_or(1) {
-                let ctx = ctx.clone();
-                thread::spawn(move || {
-                    worker::worker(&ctx);
-                });
-            }
+            worker::start_workers(
+                ctx.clone(),
+                opt.threads.unwrap_or(1) as usize,
+                opt.max_threads,
+                Duration::from_secs(opt.worker_stall_timeout),
+            );
             worker::start_timer(ctx.clone());
-            futures::future::pending().await
+            if let Some(secs) = opt.status_interval {
+                worker::start_status_line(ctx.clone(), Duration::from_secs(secs.max(1)));
+            }
+            let _admin_server = match opt.admin_addr {
+                Some(addr) => Some(admin::start_admin_server(ctx.clone(), addr).await?),
+                None => None,
+            };
+            if let Some(addr) = opt.dashboard_addr {
+                dashboard::start_dashboard(ctx.clone(), addr).await?;
+            }
+            if let Some(path) = opt.ready_file.clone() {
+                tokio::spawn(write_ready_file(ctx.clone(), path));
+            }
+
+            shutdown_signal().await?;
+            if let Some(path) = opt.ready_file.clone() {
+                remove_ready_file(path).await;
+            }
+            worker::shutdown(ctx, Duration::from_secs(opt.shutdown_drain_timeout)).await;
+            Ok(())
         }
     }
 }
+
+fn p3d_params(algo: &str) -> Result<P3dParams, ConfigError> {
+    P3dParams::new(algo).map_err(|e| ConfigError(e.to_string()))
+}
+
+/// Hex key, or a mnemonic phrase when it has spaces
+fn load_key(key: &str, option: &str, password: &str) -> Result<KeySigner, ConfigError> {
+    let key = if key.trim().contains(char::is_whitespace) {
+        KeySigner::from_mnemonic(key, password)
+    } else {
+        KeySigner::from_hex(key)
+    };
+    key.map_err(|e| ConfigError(format!("Invalid {}: {}", option, e)))
+}
+
+/// Writes the pid to `path` once the servers are up and the first params arrived
+async fn write_ready_file(ctx: Arc<MiningContext>, path: PathBuf) {
+    while ctx.cur_state.lock().unwrap().is_none() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let contents = format!("{}\n", std::process::id());
+    let target = path.clone();
+    let written = tokio::task::spawn_blocking(move || std::fs::write(target, contents))
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::new(std::io::ErrorKind::Other, e)));
+    match written {
+        Ok(_) => println!("🟢 Ready, wrote {}", path.display()),
+        Err(e) => println!("🟥 Can't write the ready file {}: {}", path.display(), e),
+    }
+}
+
+async fn remove_ready_file(path: PathBuf) {
+    // Not there if params never arrived
+    let _ = tokio::task::spawn_blocking(move || std::fs::remove_file(path)).await;
+}
+
+#[cfg(unix)]
+async fn rotate_key_on_signal(ctx: Arc<MiningContext>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut user1 = signal(SignalKind::user_defined1())?;
+    while user1.recv().await.is_some() {
+        ctx.rotate_key();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::tests::{params_node, params_response, plaintext, test_context, wait_until, MEMBER_KEY};
+
+    /// Options of a `run` that has nothing wrong with it, plus `args`
+    fn run_options(args: &[&str]) -> RunOptions {
+        let base = ["pass3d-pool", "--pool-id", "pool", "--member-id", "member", "--key", MEMBER_KEY];
+        RunOptions::from_iter_safe(base.iter().chain(args)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sane_options_have_no_problems() {
+        let opt = run_options(&["--threads", "2", "--max-threads", "4", "--obj-ids", "5-5", "--admin-addr", "127.0.0.1:9834"]);
+        assert!(config_problems(&opt).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn all_zero_keys_are_a_problem() {
+        let zero_key = format!("0x{}", "00".repeat(32));
+        let opt = run_options(&["--next-key", &zero_key]);
+        assert_eq!(config_problems(&opt).await, ["A member key is all zeros"]);
+
+        let mut opt = run_options(&[]);
+        opt.key = Some(zero_key[2..].to_string());
+        assert_eq!(config_problems(&opt).await, ["A member key is all zeros"]);
+    }
+
+    #[tokio::test]
+    async fn empty_ids_are_a_problem() {
+        let mut opt = run_options(&[]);
+        opt.member_id = String::new();
+        opt.pool_id = " ".to_string();
+        assert_eq!(config_problems(&opt).await, ["--member-id is empty", "--pool-id is empty"]);
+    }
+
+    #[tokio::test]
+    async fn url_at_our_own_servers_is_a_problem() {
+        let opt = run_options(&["--url", "http://127.0.0.1:9834", "--admin-addr", "127.0.0.1:9834"]);
+        assert_eq!(config_problems(&opt).await, ["--url points at this miner's own --admin-addr 127.0.0.1:9834"]);
+
+        let opt = run_options(&["--url", "http://127.0.0.1:9835", "--dashboard-addr", "127.0.0.1:9835"]);
+        assert_eq!(config_problems(&opt).await, ["--url points at this miner's own --dashboard-addr 127.0.0.1:9835"]);
+    }
+
+    #[tokio::test]
+    async fn url_at_our_own_wildcard_servers_is_a_problem() {
+        let opt = run_options(&["--url", "http://127.0.0.1:9834", "--admin-addr", "0.0.0.0:9834"]);
+        assert_eq!(config_problems(&opt).await, ["--url points at this miner's own --admin-addr 0.0.0.0:9834"]);
+
+        let opt = run_options(&["--url", "http://localhost:9835", "--dashboard-addr", "[::]:9835"]);
+        assert_eq!(config_problems(&opt).await, ["--url points at this miner's own --dashboard-addr [::]:9835"]);
+
+        let opt = run_options(&["--url", "http://[::1]:9834", "--admin-addr", "127.0.0.1:9834"]);
+        assert_eq!(config_problems(&opt).await, ["--url points at this miner's own --admin-addr 127.0.0.1:9834"]);
+
+        // Another port, or a host that isn't this one
+        let opt = run_options(&["--url", "http://127.0.0.1:9933", "--admin-addr", "0.0.0.0:9834"]);
+        assert!(config_problems(&opt).await.is_empty());
+        let opt = run_options(&["--url", "http://192.0.2.1:9834", "--admin-addr", "0.0.0.0:9834"]);
+        assert!(config_problems(&opt).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filters_matching_nothing_are_a_problem() {
+        let opt = run_options(&["--obj-ids", "20-10"]);
+        assert_eq!(config_problems(&opt).await, ["--obj-ids 20-10 matches no object"]);
+
+        let opt = run_options(&["--min-obj-size", "2000", "--max-obj-size", "1000"]);
+        assert_eq!(config_problems(&opt).await, ["--min-obj-size 2000 is above --max-obj-size 1000"]);
+    }
+
+    #[tokio::test]
+    async fn max_threads_below_threads_is_a_problem() {
+        let opt = run_options(&["--threads", "4", "--max-threads", "2"]);
+        assert_eq!(config_problems(&opt).await, ["--max-threads is below --threads"]);
+    }
+
+    #[tokio::test]
+    async fn only_safe_runs_refuse_to_start() {
+        let mut opt = run_options(&["--min-obj-size", "2000", "--max-obj-size", "1000"]);
+        opt.member_id = String::new();
+        assert!(check_config(&opt).await.is_ok());
+
+        opt.safe = true;
+        let e = check_config(&opt).await.unwrap_err();
+        assert_eq!(e.to_string(), "Refusing to start with 2 configuration problems, fix them or drop --safe");
+        assert!(check_config(&run_options(&["--safe"])).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ready_file_exists_from_the_first_params_until_shutdown() {
+        let (url, _node, _) = params_node(params_response("")).await;
+        let ctx = Arc::new(test_context(&url, plaintext()));
+        let path = std::env::temp_dir().join(format!("pass3d-pool-ready-{}", std::process::id()));
+        tokio::spawn(write_ready_file(ctx.clone(), path.clone()));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!path.exists());
+
+        ctx.ask_mining_params().await.unwrap();
+        wait_until(|| path.exists()).await;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{}\n", std::process::id()));
+
+        remove_ready_file(path.clone()).await;
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Bucket upper bounds in microseconds, the last one catches everything
+const BUCKETS_US: [u64; 8] = [10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000, u64::MAX];
+
+/// Lock free latency histogram, cheap enough to record on every call.
+#[derive(Default)]
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BUCKETS_US.len()],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        let idx = BUCKETS_US.iter().position(|&b| us <= b).unwrap_or(BUCKETS_US.len() - 1);
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> HistogramSnapshot {
+        let mut buckets = [0; BUCKETS_US.len()];
+        for (b, v) in buckets.iter_mut().zip(self.buckets.iter()) {
+            *b = v.load(Ordering::Relaxed);
+        }
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum_us: self.sum_us.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Bucket upper bounds of found / pool difficulty, the last one catches everything
+pub(crate) const DIFFICULTY_RATIOS: [f64; 6] = [2.0, 4.0, 16.0, 256.0, 65536.0, f64::INFINITY];
+
+/// How far found proposals exceed the pool difficulty
+#[derive(Default)]
+pub(crate) struct DifficultyDistribution {
+    buckets: [AtomicU64; DIFFICULTY_RATIOS.len()],
+}
+
+impl DifficultyDistribution {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, ratio: f64) {
+        let idx = DIFFICULTY_RATIOS.iter().position(|&b| ratio <= b).unwrap_or(DIFFICULTY_RATIOS.len() - 1);
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct HistogramSnapshot {
+    pub(crate) buckets: [u64; BUCKETS_US.len()],
+    pub(crate) count: u64,
+    pub(crate) sum_us: u64,
+}
+
+impl HistogramSnapshot {
+    /// Values recorded after `prev` was taken
+    pub(crate) fn since(&self, prev: &HistogramSnapshot) -> HistogramSnapshot {
+        let mut buckets = [0; BUCKETS_US.len()];
+        for (i, b) in buckets.iter_mut().enumerate() {
+            *b = self.buckets[i] - prev.buckets[i];
+        }
+        HistogramSnapshot {
+            buckets,
+            count: self.count - prev.count,
+            sum_us: self.sum_us - prev.sum_us,
+        }
+    }
+
+    pub(crate) fn mean_ms(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum_us as f64 / self.count as f64 / 1000.0
+    }
+
+    /// Upper bound in ms of the bucket containing the given quantile
+    pub(crate) fn quantile_ms(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let target = (self.count as f64 * q).ceil() as u64;
+        let mut acc = 0;
+        for (i, b) in self.buckets.iter().enumerate() {
+            acc += b;
+            if acc >= target {
+                return match BUCKETS_US[i] {
+                    u64::MAX => f64::INFINITY,
+                    us => us as f64 / 1000.0,
+                };
+            }
+        }
+        f64::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantiles_are_bucket_upper_bounds() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.snapshot().quantile_ms(0.5), 0.0);
+
+        for _ in 0..90 {
+            histogram.record(Duration::from_micros(50));
+        }
+        for _ in 0..9 {
+            histogram.record(Duration::from_millis(5));
+        }
+        histogram.record(Duration::from_secs(60));
+        let snapshot = histogram.snapshot();
+
+        assert_eq!(snapshot.quantile_ms(0.5), 0.1);
+        assert_eq!(snapshot.quantile_ms(0.9), 0.1);
+        assert_eq!(snapshot.quantile_ms(0.95), 10.0);
+        assert_eq!(snapshot.quantile_ms(0.99), 10.0);
+        assert_eq!(snapshot.quantile_ms(1.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn since_only_counts_later_values() {
+        let histogram = Histogram::new();
+        histogram.record(Duration::from_secs(5));
+        let prev = histogram.snapshot();
+        histogram.record(Duration::from_micros(5));
+        histogram.record(Duration::from_micros(7));
+
+        let since = histogram.snapshot().since(&prev);
+        assert_eq!(since.count, 2);
+        assert_eq!(since.mean_ms(), 0.006);
+        assert_eq!(since.quantile_ms(1.0), 0.01);
+    }
+
+    #[test]
+    fn difficulty_ratios_fall_in_their_buckets() {
+        let distribution = DifficultyDistribution::new();
+        // Bucket bounds are inclusive
+        for ratio in [1.0, 2.0, 2.5, 16.0, 17.0, 65536.0, 65537.0, 1e30] {
+            distribution.record(ratio);
+        }
+        assert_eq!(distribution.counts(), [2, 1, 1, 1, 1, 2]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket allowing `rate` events per second on average, in bursts of up to `burst`.
+pub(crate) struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate: f64) -> Self {
+        // Let a second's worth through at once, but at least one
+        let burst = rate.max(1.0);
+        Self {
+            rate,
+            burst,
+            bucket: Mutex::new(Bucket { tokens: burst, last: Instant::now() }),
+        }
+    }
+
+    pub(crate) fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Takes a token if there is one, otherwise returns how long until there is
+    pub(crate) fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    pub(crate) async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_a_burst_then_the_rate() {
+        let limiter = RateLimiter::new(5.0);
+        for _ in 0..5 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_millis(150) && wait <= Duration::from_millis(200), "{:?}", wait);
+
+        std::thread::sleep(wait + Duration::from_millis(10));
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[test]
+    fn slow_rates_still_let_one_through() {
+        let limiter = RateLimiter::new(0.5);
+        assert!(limiter.try_acquire().is_ok());
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_millis(1900), "{:?}", wait);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_token() {
+        let limiter = RateLimiter::new(20.0);
+        let start = Instant::now();
+        for _ in 0..25 {
+            limiter.acquire().await;
+        }
+        // 20 at once, the other 5 at 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(240), "{:?}", start.elapsed());
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use bip39::{Language, Mnemonic};
+use codec::Encode;
+use schnorrkel::derive::{ChainCode, Derivation};
+use schnorrkel::{ExpansionMode, MiniSecretKey, PublicKey, SecretKey, Signature};
+use substrate_bip39::mini_secret_from_entropy;
+
+const CTX: &[u8] = b"Mining pool";
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) trait Signer: Send + Sync {
+    fn sign(&self, msg: &[u8]) -> anyhow::Result<Signature>;
+
+    /// Public key if known locally
+    fn public_key(&self) -> Option<PublicKey>;
+}
+
+pub(crate) fn verify_signature(public: &PublicKey, msg: &[u8], sign: &Signature) -> bool {
+    public.verify_simple(CTX, msg, sign).is_ok()
+}
+
+/// Short printable id of a signer's public key
+pub(crate) fn fingerprint(signer: &dyn Signer) -> String {
+    match signer.public_key() {
+        Some(public) => format!("0x{}", hex::encode(&public.to_bytes()[..8])),
+        None => "remote".to_string(),
+    }
+}
+
+/// Signs with the member key held in process memory.
+pub(crate) struct KeySigner {
+    key: SecretKey,
+}
+
+impl KeySigner {
+    pub(crate) fn from_hex(key: &str) -> anyhow::Result<Self> {
+        let key = key.replacen("0x", "", 1);
+        let key_data = hex::decode(&key[..])?;
+        let key = MiniSecretKey::from_bytes(&key_data[..])
+            .map_err(|e| anyhow::anyhow!("Invalid key: {:?}", e))?
+            .expand(ExpansionMode::Ed25519);
+
+        Ok(Self { key })
+    }
+
+    /// Same key as `inspect` prints for the phrase, then derived along the substrate style
+    /// path following it: `//hard` and `/soft` junctions, numeric ones taken as u64 like
+    /// substrate does. The password is the one substrate tools take after `///`.
+    pub(crate) fn from_mnemonic(uri: &str, password: &str) -> anyhow::Result<Self> {
+        let uri = uri.trim();
+        if uri.contains("///") {
+            anyhow::bail!("Invalid mnemonic: pass the password with --key-password rather than after ///");
+        }
+        let (phrase, mut path) = uri.split_at(uri.find('/').unwrap_or(uri.len()));
+        let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+            .map_err(|e| anyhow::anyhow!("Invalid mnemonic: {}", e))?;
+        let mini_key = mini_secret_from_entropy(mnemonic.entropy(), password)
+            .map_err(|e| anyhow::anyhow!("Invalid mnemonic: {:?}", e))?;
+        let mut signer = Self::from_hex(&hex::encode(mini_key.to_bytes()))?;
+
+        while let Some(junction) = path.strip_prefix('/') {
+            let (hard, junction) = match junction.strip_prefix('/') {
+                Some(junction) => (true, junction),
+                None => (false, junction),
+            };
+            let (code, rest) = junction.split_at(junction.find('/').unwrap_or(junction.len()));
+            let chain_code = chain_code(code)?;
+            signer.key = if hard {
+                signer.key.hard_derive_mini_secret_key(Some(chain_code), b"").0.expand(ExpansionMode::Ed25519)
+            } else {
+                signer.key.derived_key_simple(chain_code, b"").0
+            };
+            path = rest;
+        }
+        Ok(signer)
+    }
+}
+
+/// Chain code of a derivation junction, its SCALE encoding zero padded to 32 bytes.
+/// Substrate hashes longer encodings with blake2, which isn't available here.
+fn chain_code(code: &str) -> anyhow::Result<ChainCode> {
+    if code.is_empty() {
+        anyhow::bail!("Invalid mnemonic: empty derivation junction");
+    }
+    let encoded = match code.parse::<u64>() {
+        Ok(index) => index.encode(),
+        Err(_) => code.encode(),
+    };
+    if encoded.len() > 32 {
+        anyhow::bail!("Invalid mnemonic: derivation junction {} is too long, up to 31 bytes are supported", code);
+    }
+    let mut chain_code = [0u8; 32];
+    chain_code[..encoded.len()].copy_from_slice(&encoded);
+    Ok(ChainCode(chain_code))
+}
+
+impl Signer for KeySigner {
+    fn sign(&self, msg: &[u8]) -> anyhow::Result<Signature> {
+        Ok(self.key.sign_simple(CTX, msg, &self.key.to_public()))
+    }
+
+    fn public_key(&self) -> Option<PublicKey> {
+        Some(self.key.to_public())
+    }
+}
+
+/// Delegates signing to an external service (HSM bridge etc.) so the key never enters the process.
+///
+/// Protocol: one connection per signature, the client sends the hex encoded message
+/// followed by a newline and the service replies with the hex encoded signature line.
+/// The service is expected to sign with the "Mining pool" schnorrkel context.
+pub(crate) struct RemoteSigner {
+    addr: String,
+}
+
+impl RemoteSigner {
+    pub(crate) fn new(addr: String) -> Self {
+        Self { addr }
+    }
+
+    /// Tries every address the service resolves to, none may hold up signing for long
+    fn connect(&self) -> anyhow::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, REMOTE_SIGNER_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(anyhow::anyhow!("Can't connect to the remote signer {}: {}", self.addr, e)),
+            None => Err(anyhow::anyhow!("Remote signer address {} resolves to nothing", self.addr)),
+        }
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn sign(&self, msg: &[u8]) -> anyhow::Result<Signature> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(REMOTE_SIGNER_TIMEOUT))?;
+        stream.set_write_timeout(Some(REMOTE_SIGNER_TIMEOUT))?;
+        writeln!(stream, "{}", hex::encode(msg))?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let sign_data = hex::decode(line.trim().replacen("0x", "", 1))?;
+
+        Signature::from_bytes(&sign_data)
+            .map_err(|e| anyhow::anyhow!("Invalid signature from remote signer: {:?}", e))
+    }
+
+    fn public_key(&self) -> Option<PublicKey> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Substrate's well known development phrase
+    const DEV_PHRASE: &str = "bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+    fn public_hex(signer: &KeySigner) -> String {
+        hex::encode(signer.public_key().unwrap().to_bytes())
+    }
+
+    #[test]
+    fn mnemonic_gives_the_substrate_key() {
+        let signer = KeySigner::from_mnemonic(DEV_PHRASE, "").unwrap();
+        assert_eq!(public_hex(&signer), "46ebddef8cd9bb167dc30878d7113b7e168e6f0646beffd77d69d39bad76b47a");
+    }
+
+    #[test]
+    fn hard_junctions_give_the_dev_accounts() {
+        let alice = KeySigner::from_mnemonic(&format!("{}//Alice", DEV_PHRASE), "").unwrap();
+        assert_eq!(public_hex(&alice), "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d");
+        let bob = KeySigner::from_mnemonic(&format!("{}//Bob", DEV_PHRASE), "").unwrap();
+        assert_eq!(public_hex(&bob), "8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48");
+    }
+
+    #[test]
+    fn soft_junctions_match_public_derivation() {
+        let alice = KeySigner::from_mnemonic(&format!("{}//Alice", DEV_PHRASE), "").unwrap();
+        let child = KeySigner::from_mnemonic(&format!("{}//Alice/stash/1", DEV_PHRASE), "").unwrap();
+
+        let public = alice.public_key().unwrap();
+        let public = public.derived_key_simple(chain_code("stash").unwrap(), b"").0;
+        let public = public.derived_key_simple(chain_code("1").unwrap(), b"").0;
+        assert_eq!(child.public_key().unwrap(), public);
+    }
+
+    #[test]
+    fn bad_paths_are_rejected() {
+        assert!(KeySigner::from_mnemonic(&format!("{}//", DEV_PHRASE), "").is_err());
+        assert!(KeySigner::from_mnemonic(&format!("{}///password", DEV_PHRASE), "").is_err());
+        assert!(KeySigner::from_mnemonic(&format!("{}//{}", DEV_PHRASE, "x".repeat(32)), "").is_err());
+    }
+
+    #[test]
+    fn signatures_verify() {
+        let signer = KeySigner::from_mnemonic(DEV_PHRASE, "").unwrap();
+        let sign = signer.sign(b"message").unwrap();
+        assert!(verify_signature(&signer.public_key().unwrap(), b"message", &sign));
+        assert!(!verify_signature(&signer.public_key().unwrap(), b"other message", &sign));
+    }
+
+    /// Signing service speaking the remote signer protocol, for `connections` requests
+    fn mock_remote_signer(key: KeySigner, connections: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let sign = key.sign(&hex::decode(line.trim()).unwrap()).unwrap();
+                writeln!(stream, "0x{}", hex::encode(sign.to_bytes())).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn remote_signer_signs_with_the_service_key() {
+        let key = KeySigner::from_mnemonic(DEV_PHRASE, "").unwrap();
+        let public = key.public_key().unwrap();
+        let signer = RemoteSigner::new(mock_remote_signer(key, 2));
+
+        assert!(signer.public_key().is_none());
+        for msg in [&b"message"[..], &[0u8, 255, 10, 13][..]] {
+            let sign = signer.sign(msg).unwrap();
+            assert!(verify_signature(&public, msg, &sign));
+        }
+    }
+
+    #[test]
+    fn remote_signer_rejects_garbage() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let signer = RemoteSigner::new(listener.local_addr().unwrap().to_string());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            writeln!(stream, "not a signature").unwrap();
+        });
+        assert!(signer.sign(b"message").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::rpc::{MiningContext, MiningObj, ObjSource};
+use crate::worker::{create_mining_obj, create_mining_obj_with};
+
+/// Supplies objects to a mining worker.
+pub(crate) trait ObjectSource: Send {
+    /// Next object to mine, `None` when nothing is available right now.
+    fn next_object(&mut self) -> Option<MiningObj>;
+}
+
+/// Generates a fresh randomly dented sphere for every call.
+pub(crate) struct GeneratedSource;
+
+impl ObjectSource for GeneratedSource {
+    fn next_object(&mut self) -> Option<MiningObj> {
+        Some(MiningObj {
+            obj_id: 1,
+            obj: create_mining_obj(),
+            source: ObjSource::Text,
+            deadline: None,
+            difficulty: None,
+        })
+    }
+}
+
+/// Generates a reproducible sequence of dented spheres, for benchmarking only.
+pub(crate) struct SeededSource {
+    rng: StdRng,
+}
+
+impl SeededSource {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl ObjectSource for SeededSource {
+    fn next_object(&mut self) -> Option<MiningObj> {
+        Some(MiningObj {
+            obj_id: 1,
+            obj: create_mining_obj_with(&mut self.rng),
+            source: ObjSource::Text,
+            deadline: None,
+            difficulty: None,
+        })
+    }
+}
+
+/// Objects handed out by the pool node first, generated ones when there are none.
+///
+/// With `prefetch` above 1 it takes up to that many objects per lock, but never more
+/// than its share of the queue, so other workers aren't left idle. Objects not mined
+/// yet go back to the front of in_queue when the source is dropped.
+pub(crate) struct PoolSource {
+    ctx: Arc<MiningContext>,
+    fallback: GeneratedSource,
+    prefetched: VecDeque<MiningObj>,
+}
+
+impl PoolSource {
+    pub(crate) fn new(ctx: Arc<MiningContext>) -> Self {
+        Self { ctx, fallback: GeneratedSource, prefetched: VecDeque::new() }
+    }
+
+    fn pop(&mut self) -> Option<MiningObj> {
+        if self.prefetched.is_empty() {
+            let workers = self.ctx.workers.load(Ordering::Relaxed).max(1);
+            let mut in_queue = self.ctx.in_queue.lock().unwrap();
+            let batch = self.ctx.prefetch.min(in_queue.len().div_ceil(workers));
+            for obj in in_queue.drain(..batch) {
+                self.ctx.prefetched_objects.fetch_add(1, Ordering::Relaxed);
+                self.ctx.prefetched_bytes.fetch_add(obj.obj.len(), Ordering::Relaxed);
+                self.prefetched.push_back(obj);
+            }
+        }
+        let obj = self.prefetched.pop_front()?;
+        self.release(&obj);
+        Some(obj)
+    }
+
+    /// Stops counting an object as prefetched
+    fn release(&self, obj: &MiningObj) {
+        self.ctx.prefetched_objects.fetch_sub(1, Ordering::Relaxed);
+        self.ctx.prefetched_bytes.fetch_sub(obj.obj.len(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for PoolSource {
+    fn drop(&mut self) {
+        let mut in_queue = self.ctx.in_queue.lock().unwrap();
+        while let Some(obj) = self.prefetched.pop_back() {
+            self.release(&obj);
+            in_queue.push_front(obj);
+        }
+    }
+}
+
+impl ObjectSource for PoolSource {
+    fn next_object(&mut self) -> Option<MiningObj> {
+        loop {
+            match self.pop() {
+                Some(obj) if obj.is_expired() => {
+                    self.ctx.expired_objects.fetch_add(1, Ordering::Relaxed);
+                    println!("⌛ Skipping object {}, past its deadline", obj.obj_id);
+                }
+                Some(obj) => return Some(obj),
+                None => return self.fallback.next_object(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+    use crate::rpc::MiningOptions;
+    use crate::rpc::tests::{plaintext, test_context};
+
+    fn pool_obj(obj_id: u64) -> MiningObj {
+        MiningObj { obj_id, obj: vec![b'v'; 10], source: ObjSource::Text, deadline: None, difficulty: None }
+    }
+
+    #[test]
+    fn seeded_sources_repeat_their_objects() {
+        let objects = |seed| {
+            let mut source = SeededSource::new(seed);
+            (0..3).map(|_| source.next_object().unwrap().obj).collect::<Vec<_>>()
+        };
+        assert_eq!(objects(7), objects(7));
+        assert_ne!(objects(7), objects(8));
+        // Each one is a fresh sphere
+        let run = objects(7);
+        assert_ne!(run[0], run[1]);
+    }
+
+    #[tokio::test]
+    async fn prefetched_objects_are_counted_until_mined_or_returned() {
+        let ctx = Arc::new(test_context("http://127.0.0.1:1", MiningOptions { prefetch: 4, ..plaintext() }));
+        ctx.in_queue.lock().unwrap().extend((0..3).map(pool_obj));
+
+        let mut source = PoolSource::new(ctx.clone());
+        assert_eq!(source.next_object().unwrap().obj_id, 0);
+        assert_eq!(ctx.prefetched_objects.load(Ordering::Relaxed), 2);
+        assert_eq!(ctx.prefetched_bytes.load(Ordering::Relaxed), 20);
+        assert!(ctx.in_queue.lock().unwrap().is_empty());
+
+        drop(source);
+        assert_eq!(ctx.prefetched_objects.load(Ordering::Relaxed), 0);
+        assert_eq!(ctx.prefetched_bytes.load(Ordering::Relaxed), 0);
+        let ids: Vec<u64> = ctx.in_queue.lock().unwrap().iter().map(|obj| obj.obj_id).collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn objects_past_their_deadline_are_skipped() {
+        let ctx = Arc::new(test_context("http://127.0.0.1:1", MiningOptions { prefetch: 4, ..plaintext() }));
+        let in_an_hour = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 3600;
+        ctx.in_queue.lock().unwrap().extend([
+            MiningObj { deadline: Some(1), ..pool_obj(0) },
+            MiningObj { deadline: Some(in_an_hour), ..pool_obj(1) },
+            MiningObj { deadline: Some(2), ..pool_obj(2) },
+            pool_obj(3),
+        ]);
+
+        let mut source = PoolSource::new(ctx.clone());
+        assert_eq!(source.next_object().unwrap().obj_id, 1);
+        assert_eq!(source.next_object().unwrap().obj_id, 3);
+        assert_eq!(ctx.expired_objects.load(Ordering::Relaxed), 2);
+        assert_eq!(ctx.prefetched_objects.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rpc::{AlgoType, MiningContext};
+
+/// Bumped on any incompatible change of `StatsSnapshot`
+pub(crate) const STATS_VERSION: u32 = 1;
+
+/// Point in time statistics, served by the admin RPC `pool_stats` method.
+/// Fields are only ever added, see README for their meaning.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct StatsSnapshot {
+    pub(crate) version: u32,
+    pub(crate) uptime_secs: u64,
+    pub(crate) algo: String,
+    pub(crate) hashrate: f64,
+    pub(crate) iterations: usize,
+    pub(crate) bad_objects: usize,
+    pub(crate) dupe_objects: usize,
+    pub(crate) in_queue: usize,
+    pub(crate) out_queue: usize,
+    pub(crate) inflight_submissions: usize,
+    pub(crate) pushed: usize,
+    pub(crate) submit_rate: f64,
+    pub(crate) submit_rate_limit: Option<f64>,
+    pub(crate) accepted: usize,
+    pub(crate) rejected: usize,
+    pub(crate) unknown: usize,
+    pub(crate) stale_dropped: usize,
+    pub(crate) floor_filtered: usize,
+    pub(crate) found_shares: usize,
+    pub(crate) found_blocks: usize,
+    pub(crate) found_difficulty: Vec<u64>,
+    pub(crate) filtered_objects: usize,
+    pub(crate) expired_objects: usize,
+    pub(crate) oversize_rejected: usize,
+    pub(crate) malformed_results: usize,
+    pub(crate) compute_errors: usize,
+    pub(crate) compute_errors_by_algo: BTreeMap<String, usize>,
+    pub(crate) memory_usage: usize,
+    pub(crate) memory_drops: usize,
+    pub(crate) workers: usize,
+    pub(crate) worker_restarts: usize,
+    pub(crate) member_registered: bool,
+    pub(crate) connected: bool,
+    pub(crate) paused: bool,
+    pub(crate) node_lag: Option<u64>,
+    pub(crate) shadow_divergences: usize,
+    pub(crate) double_checks: usize,
+    pub(crate) double_check_failures: usize,
+    pub(crate) logged_in: bool,
+}
+
+impl MiningContext {
+    pub(crate) fn stats_snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            version: STATS_VERSION,
+            uptime_secs: self.started.elapsed().as_secs(),
+            algo: self.p3d_params().algo.as_str().into(),
+            hashrate: f64::from_bits(self.hashrate.load(Ordering::Relaxed)),
+            iterations: self.iterations_count.load(Ordering::Relaxed),
+            bad_objects: self.bad_objects.load(Ordering::Relaxed),
+            dupe_objects: self.dupe_objects.load(Ordering::Relaxed),
+            in_queue: self.in_queue.lock().unwrap().len(),
+            out_queue: self.out_queue.lock().unwrap().len(),
+            inflight_submissions: self.inflight_submissions.load(Ordering::Relaxed),
+            pushed: self.pushed_objects.load(Ordering::Relaxed),
+            submit_rate: f64::from_bits(self.submit_rate.load(Ordering::Relaxed)),
+            submit_rate_limit: self.submit_limiter.as_ref().map(|limiter| limiter.rate()),
+            accepted: self.accepted_objects.load(Ordering::Relaxed),
+            rejected: self.rejected_objects.load(Ordering::Relaxed),
+            unknown: self.unknown_outcomes.load(Ordering::Relaxed),
+            stale_dropped: self.stale_proposals.load(Ordering::Relaxed),
+            floor_filtered: self.floor_filtered.load(Ordering::Relaxed),
+            found_shares: self.found_shares.load(Ordering::Relaxed),
+            found_blocks: self.found_blocks.load(Ordering::Relaxed),
+            found_difficulty: self.found_difficulty.counts(),
+            filtered_objects: self.filtered_objects.load(Ordering::Relaxed),
+            expired_objects: self.expired_objects.load(Ordering::Relaxed),
+            oversize_rejected: self.oversize_rejected.load(Ordering::Relaxed),
+            malformed_results: self.malformed_results.load(Ordering::Relaxed),
+            compute_errors: self.compute_errors.iter().map(|errors| errors.load(Ordering::Relaxed)).sum(),
+            compute_errors_by_algo: AlgoType::ALL
+                .iter()
+                .map(|algo| (algo.as_str().to_string(), self.compute_errors[algo.index()].load(Ordering::Relaxed)))
+                .collect(),
+            memory_usage: self.memory_usage(),
+            memory_drops: self.memory_drops.load(Ordering::Relaxed),
+            workers: self.workers.load(Ordering::Relaxed),
+            worker_restarts: self.worker_restarts.load(Ordering::Relaxed),
+            member_registered: self.member_registered.load(Ordering::Relaxed),
+            connected: self.connected.load(Ordering::Relaxed),
+            paused: self.paused.load(Ordering::Relaxed),
+            node_lag: *self.node_lag.lock().unwrap(),
+            shadow_divergences: self.shadow_divergences.load(Ordering::Relaxed),
+            double_checks: self.double_checks.load(Ordering::Relaxed),
+            double_check_failures: self.double_check_failures.load(Ordering::Relaxed),
+            logged_in: self.logged_in(),
+        }
+    }
+}
+
+impl StatsSnapshot {
+    /// One line summary for `--status-interval`
+    pub(crate) fn status_line(&self) -> String {
+        let uptime = self.uptime_secs;
+        format!(
+            "📊 {:.2} it/s | {} accepted | {} rejected | {} queued | up {}h{:02}m{:02}s",
+            self.hashrate,
+            self.accepted,
+            self.rejected,
+            self.in_queue,
+            uptime / 3600,
+            uptime / 60 % 60,
+            uptime % 60,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rpc::tests::{plaintext, test_context};
+
+    #[test]
+    fn status_line_has_the_headline_numbers() {
+        let ctx = test_context("http://127.0.0.1:1", plaintext());
+        let mut stats = ctx.stats_snapshot();
+        stats.hashrate = 41.2;
+        stats.accepted = 12;
+        stats.rejected = 1;
+        stats.in_queue = 3;
+        stats.uptime_secs = 2 * 3600 + 5 * 60 + 9;
+        assert_eq!(stats.status_line(), "📊 41.20 it/s | 12 accepted | 1 rejected | 3 queued | up 2h05m09s");
+    }
+}
@@ -2,10 +2,11 @@ use std::convert::TryInto;
 use std::f32::consts::PI;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
-use std::time::Duration;
-use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use std::sync::atomic::AtomicUsize;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Write;
 
 use ansi_term::Style;
@@ -15,15 +16,27 @@ use genmesh::{MapVertex, Triangle, Triangulate};
 use genmesh::generators::{IndexedPolygon, SharedVertex, SphereUv};
 use p3d::p3d_process;
 use primitive_types::{H256, U256};
+use futures::StreamExt;
 use rand::prelude::*;
 use sha3::{Digest, Sha3_256};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time;
-use crate::rpc::{MiningObj, MiningProposal, AlgoType};
+use crate::rpc::{MiningObj, MiningProposal, AlgoType, InflightGuard, PushOutcome};
+use crate::source::{GeneratedSource, ObjectSource, PoolSource, SeededSource};
 use rayon::prelude::*;
 use super::MiningContext;
 use super::P3dParams;
 use super::rpc::MiningParams;
+use crate::eventlog::PipelineEvent;
+use crate::metrics::{HistogramSnapshot, DIFFICULTY_RATIOS};
+use crate::signer::{verify_signature, KeySigner, Signer};
 const ASK_MINING_PARAMS_PERIOD: Duration = Duration::from_secs(10);
+const WATCHDOG_PERIOD: Duration = Duration::from_secs(5);
+/// Watchdog ticks of in_queue depth samples the autoscaler looks at. Scaling down
+/// needs a longer quiet period than scaling up needs a backlog, so it doesn't flap.
+const SCALE_UP_SAMPLES: usize = 3;
+const SCALE_DOWN_SAMPLES: usize = 12;
+const SELFTEST_SEED: u64 = 3;
 
 #[derive(Encode)]
 pub struct DoubleHash {
@@ -58,11 +71,208 @@ pub fn get_hash_difficulty(hash: &H256) -> U256 {
     max / num_hash
 }
 
-pub(crate) fn worker(ctx: &MiningContext) {
-    let P3dParams { algo, sect, grid } = ctx.p3d_params.clone();
-    let mut processed_hashes: HashSet<H256> = HashSet::new(); 
+/// Difficulty the node derives for `obj_hash` when checking it against `target`
+fn work_difficulty(pre_hash: H256, obj_hash: H256, target: U256) -> U256 {
+    let poscan_hash = DoubleHash { pre_hash, obj_hash }.calc_hash();
+    get_hash_difficulty(&Compute { difficulty: target, pre_hash, poscan_hash }.get_work())
+}
+
+/// Approximate value of a difficulty, precise in the top 64 bits
+pub fn difficulty_to_f64(difficulty: &U256) -> f64 {
+    let bits = difficulty.bits();
+    if bits <= 64 {
+        difficulty.low_u64() as f64
+    } else {
+        let shift = bits - 64;
+        (*difficulty >> shift).low_u64() as f64 * 2f64.powi(shift as i32)
+    }
+}
+
+/// Difficulty as the equivalent number of leading zero bits of the hash
+pub fn difficulty_bits(difficulty: &U256) -> f64 {
+    if difficulty.is_zero() {
+        return 0.0;
+    }
+    difficulty_to_f64(difficulty).log2()
+}
+
+/// Human readable difficulty, e.g. `12.35M (23.6 bits)`
+pub fn format_difficulty(difficulty: &U256) -> String {
+    const UNITS: [&str; 7] = ["", "K", "M", "G", "T", "P", "E"];
+
+    let mut value = difficulty_to_f64(difficulty);
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.2}{} ({:.1} bits)", value, UNITS[unit], difficulty_bits(difficulty))
+}
+
+/// Every p3d algorithm returns hex encoded 32 byte hashes, anything else means the
+/// object went through the wrong algorithm or parameters and the node would reject it
+fn check_hashes(hashes: &[String]) -> Result<(), String> {
+    for hash in hashes {
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("unexpected hash {:?}", hash));
+        }
+    }
+    Ok(())
+}
+
+/// Progress of a worker thread, checked by the watchdog
+pub(crate) struct WorkerState {
+    id: usize,
+    last_progress_ms: AtomicU64,
+    abandoned: AtomicBool,
+}
+
+impl WorkerState {
+    fn new(id: usize, ctx: &MiningContext) -> Self {
+        let state = Self {
+            id,
+            last_progress_ms: AtomicU64::new(0),
+            abandoned: AtomicBool::new(false),
+        };
+        state.touch(ctx);
+        state
+    }
+
+    fn touch(&self, ctx: &MiningContext) {
+        self.last_progress_ms.store(ctx.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn idle(&self, ctx: &MiningContext) -> Duration {
+        let now = ctx.started.elapsed().as_millis() as u64;
+        Duration::from_millis(now.saturating_sub(self.last_progress_ms.load(Ordering::Relaxed)))
+    }
+
+    fn is_abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn spawn_worker(ctx: Arc<MiningContext>, id: usize) -> Arc<WorkerState> {
+    let state = Arc::new(WorkerState::new(id, &ctx));
+    let worker_state = state.clone();
+    thread::spawn(move || {
+        let mut source = PoolSource::new(ctx.clone());
+        worker(&ctx, &mut source, &worker_state);
+    });
+    state
+}
+
+/// Worker count between `min` and `max` from the in_queue depth, sampled once per watchdog tick
+struct Autoscaler {
+    min: usize,
+    max: usize,
+    depths: VecDeque<usize>,
+}
+
+impl Autoscaler {
+    fn new(min: usize, max: usize) -> Self {
+        Self { min, max, depths: VecDeque::with_capacity(SCALE_DOWN_SAMPLES) }
+    }
+
+    /// Workers to run next, one more or one less than `workers` at a time
+    fn sample(&mut self, depth: usize, workers: usize) -> usize {
+        if self.depths.len() == SCALE_DOWN_SAMPLES {
+            self.depths.pop_front();
+        }
+        self.depths.push_back(depth);
+
+        let backlog = self.depths.len() >= SCALE_UP_SAMPLES
+            && self.depths.iter().rev().take(SCALE_UP_SAMPLES).all(|&depth| depth > workers);
+        let quiet = self.depths.len() == SCALE_DOWN_SAMPLES && self.depths.iter().all(|&depth| depth == 0);
+        let target = if backlog && workers < self.max {
+            workers + 1
+        } else if quiet && workers > self.min {
+            workers - 1
+        } else {
+            return workers;
+        };
+        // The new count gets a fresh window before it's changed again
+        self.depths.clear();
+        target
+    }
+}
+
+/// Spawns the workers and respawns any of them making no progress for `stall_timeout`.
+/// A stuck thread can't be killed, it's abandoned and exits whenever its compute returns.
+///
+/// With `max_threads` the count goes up to it, one worker per tick, while objects from
+/// the node keep piling up in in_queue and back down to `threads` once it stays empty.
+pub(crate) fn start_workers(ctx: Arc<MiningContext>, threads: usize, max_threads: Option<usize>, stall_timeout: Duration) {
+    let mut workers: Vec<Arc<WorkerState>> = (0..threads)
+        .map(|id| spawn_worker(ctx.clone(), id))
+        .collect();
+    ctx.workers.store(workers.len(), Ordering::Relaxed);
+
+    let mut autoscaler = max_threads.map(|max_threads| Autoscaler::new(threads, max_threads));
+    tokio::spawn(async move {
+        let mut interval = time::interval(WATCHDOG_PERIOD);
+        while !ctx.shutdown.load(Ordering::Relaxed) {
+            interval.tick().await;
+
+            if let Some(autoscaler) = &mut autoscaler {
+                let depth = ctx.in_queue.lock().unwrap().len();
+                let target = autoscaler.sample(depth, workers.len());
+                if target > workers.len() {
+                    workers.push(spawn_worker(ctx.clone(), workers.len()));
+                    println!("📈 Input queue backing up, scaled up to {} workers", workers.len());
+                } else if target < workers.len() {
+                    if let Some(state) = workers.pop() {
+                        state.abandoned.store(true, Ordering::Relaxed);
+                    }
+                    println!("📉 Input queue idle, scaled down to {} workers", workers.len());
+                }
+                ctx.workers.store(workers.len(), Ordering::Relaxed);
+            }
+
+            for state in workers.iter_mut() {
+                let idle = state.idle(&ctx);
+                if idle > stall_timeout {
+                    println!("🟥 Worker {} made no progress for {:?}, restarting it", state.id, idle);
+                    state.abandoned.store(true, Ordering::Relaxed);
+                    *state = spawn_worker(ctx.clone(), state.id);
+                    ctx.worker_restarts.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+}
 
+/// Runs `compute` again after an error, up to `compute_retries` times, counting
+/// every error against the algorithm
+fn compute_with_retries<E: std::fmt::Debug>(
+    ctx: &MiningContext,
+    state: &WorkerState,
+    obj_id: u64,
+    algo: &AlgoType,
+    mut compute: impl FnMut() -> Result<Vec<String>, E>,
+) -> Result<Vec<String>, E> {
+    let mut attempt = 0;
     loop {
+        let res = compute();
+        if let Err(e) = &res {
+            ctx.compute_errors[algo.index()].fetch_add(1, Ordering::Relaxed);
+            if attempt < ctx.compute_retries && !state.is_abandoned() {
+                attempt += 1;
+                println!("🟥 Compute error on obj_id {}: {:?}, retrying ({}/{})", obj_id, e, attempt, ctx.compute_retries);
+                continue;
+            }
+            println!("🟥 Compute error on obj_id {}: {:?}, dropping the object", obj_id, e);
+        }
+        return res;
+    }
+}
+
+pub(crate) fn worker(ctx: &MiningContext, source: &mut dyn ObjectSource, state: &WorkerState) {
+    let mut processed_hashes: HashSet<H256> = HashSet::new(); 
+
+    while !ctx.shutdown.load(Ordering::Relaxed) && !state.is_abandoned() {
+        state.touch(ctx);
+        let P3dParams { algo, sect, grid } = ctx.p3d_params();
         let mining_params = {
             let params_lock = ctx.cur_state.lock().unwrap();
             if let Some(mp) = (*params_lock).clone() {
@@ -87,29 +297,57 @@ pub(crate) fn worker(ctx: &MiningContext) {
         };
         let rot = rot_hash.encode()[0..4].try_into().ok();
 
-        let mining_obj: MiningObj = MiningObj {
-            obj_id: 1,
-            obj: create_mining_obj(),
+        // Queued objects already count towards the pipeline, mining them doesn't grow it
+        let queued = !ctx.in_queue.lock().unwrap().is_empty() || ctx.prefetched_objects.load(Ordering::Relaxed) > 0;
+        if (ctx.pipeline_full() && !queued) || ctx.paused.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+
+        let mining_obj: MiningObj = match source.next_object() {
+            Some(obj) => obj,
+            None => {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
         };
+        let pow_difficulty = mining_obj.difficulty.unwrap_or(pow_difficulty);
 
-        let res_hashes = p3d_process(
-            mining_obj.obj.as_slice(),
-            algo.as_p3d_algo(),
-            grid as i16,
-            sect as i16,
-            rot,
-        );
+        ctx.computing_objects.fetch_add(1, Ordering::Relaxed);
+        ctx.log_event(PipelineEvent::ComputeStarted { obj_id: mining_obj.obj_id, pre_hash });
+        let res_hashes = compute_with_retries(ctx, state, mining_obj.obj_id, &algo, || {
+            p3d_process(
+                mining_obj.obj.as_slice(),
+                algo.as_p3d_algo(),
+                grid as i16,
+                sect as i16,
+                rot,
+            )
+        });
+        ctx.computing_objects.fetch_sub(1, Ordering::Relaxed);
+        ctx.log_event(PipelineEvent::ComputeFinished {
+            obj_id: mining_obj.obj_id,
+            obj_hash: res_hashes.as_ref().ok().and_then(|h| h.first()).and_then(|h| H256::from_str(h).ok()),
+        });
+
+        if state.is_abandoned() {
+            return;
+        }
 
-        let (first_hash, obj_hash, poscan_hash) = match res_hashes {
+        let (first_hash, obj_hash) = match res_hashes {
             Ok(hashes) if !hashes.is_empty() => {
+                if let Err(e) = check_hashes(&hashes) {
+                    ctx.malformed_results.fetch_add(1, Ordering::Relaxed);
+                    println!("🟥 Dropping {} result of obj_id {}: {}", algo.as_str(), mining_obj.obj_id, e);
+                    continue;
+                }
                 let first_hash = hashes[0].clone();
                 let obj_hash = H256::from_str(&first_hash).unwrap();
                 if processed_hashes.contains(&obj_hash) {
                     continue;
                 }
-                let poscan_hash = DoubleHash { pre_hash, obj_hash }.calc_hash();
                 processed_hashes.insert(obj_hash.clone());
-                (first_hash, obj_hash, poscan_hash)
+                (first_hash, obj_hash)
             },
             _ => {
                 continue;
@@ -121,58 +359,316 @@ pub(crate) fn worker(ctx: &MiningContext) {
             ctx.bad_objects.fetch_add(1, Ordering::Relaxed);
         }
 
-        let mut lock = ctx.seen_objects.lock().unwrap();
-        if !(*lock).insert(obj_hash.clone()) {
+        let is_new = ctx.seen_objects.lock().unwrap().insert(obj_hash);
+        if !is_new {
             ctx.dupe_objects.fetch_add(1, Ordering::Relaxed);
             continue;
         }
 
-        for difficulty in [pow_difficulty, win_difficulty] {
-            let comp = Compute {
-                difficulty,
-                pre_hash,
-                poscan_hash,
-            };
-
-            let diff = get_hash_difficulty(&comp.get_work());
+        let share_diff = work_difficulty(pre_hash, obj_hash, pow_difficulty);
+        let block_diff = work_difficulty(pre_hash, obj_hash, win_difficulty);
+        let is_share = share_diff >= pow_difficulty && !ctx.blocks_only;
+        if is_share {
+            ctx.found_difficulty.record(difficulty_to_f64(&share_diff) / difficulty_to_f64(&pow_difficulty));
+        }
+        // A block is the same submission as a share, it's queued once either way
+        let (diff, is_block) = if block_diff >= win_difficulty {
+            (block_diff, true)
+        } else if is_share {
+            (share_diff, false)
+        } else {
+            continue;
+        };
 
-            if diff >= difficulty {
-                let prop = MiningProposal {
-                    params: mining_params.clone(),
-                    hash: obj_hash,
-                    obj_id: mining_obj.obj_id,
-                    obj: mining_obj.obj.clone(),
-                };
-                ctx.push_to_queue(prop);
-                println!("💎 Hash > Pool Difficulty: {} > {} (win: {})",
-                         Style::new().bold().paint(format!("{:.2}", &diff)),
-                         &pow_difficulty,
-                         &win_difficulty,
-                );
-                println!("obj_hash: {:?}", obj_hash);
+        if let Some(floor) = ctx.difficulty_floor(win_difficulty) {
+            if diff < floor {
+                ctx.floor_filtered.fetch_add(1, Ordering::Relaxed);
+                continue;
             }
         }
+        // A rare compute bug on a block quality proposal costs more than the recompute.
+        // The difficulty is derived again from the second hash, the way the node does it.
+        if ctx.double_check_difficulty.is_some_and(|threshold| diff >= threshold) {
+            ctx.double_checks.fetch_add(1, Ordering::Relaxed);
+            let target = if is_block { win_difficulty } else { pow_difficulty };
+            let rechecked = p3d_process(mining_obj.obj.as_slice(), algo.as_p3d_algo(), grid as i16, sect as i16, rot)
+                .ok()
+                .and_then(|hashes| hashes.first().and_then(|hash| H256::from_str(hash).ok()))
+                .map(|obj_hash| work_difficulty(pre_hash, obj_hash, target));
+            if rechecked != Some(diff) {
+                ctx.double_check_failures.fetch_add(1, Ordering::Relaxed);
+                println!("🟥 Second compute of obj_id {} doesn't give difficulty {}, dropping the proposal", mining_obj.obj_id, format_difficulty(&diff));
+                continue;
+            }
+        }
+
+        let prop = MiningProposal {
+            // Submitted with the difficulty it was mined to
+            params: MiningParams { pow_difficulty, ..mining_params.clone() },
+            algo: algo.clone(),
+            hash: obj_hash,
+            obj_id: mining_obj.obj_id,
+            obj: mining_obj.obj.clone(),
+            difficulty: diff,
+            is_block,
+        };
+        ctx.log_event(PipelineEvent::ProposalFound {
+            obj_id: mining_obj.obj_id,
+            obj_hash,
+            pre_hash,
+            difficulty: diff,
+        });
+        ctx.push_to_queue(prop);
+        println!("💎 Hash > {} Difficulty: {} > {} (win: {})",
+                 if is_block { "Network" } else { "Pool" },
+                 Style::new().bold().paint(format_difficulty(&diff)),
+                 format_difficulty(&pow_difficulty),
+                 format_difficulty(&win_difficulty),
+        );
+        println!("obj_hash: {:?}", obj_hash);
+    }
+}
+
+
+/// Mines generated objects without a pool for `duration` and reports the speed.
+/// With a seed every thread generates the same object sequence on each run,
+/// which is only meant to make benchmark runs comparable.
+pub(crate) fn bench(p3d_params: P3dParams, threads: usize, duration: Duration, seed: Option<u64>) {
+    let P3dParams { algo, sect, grid } = p3d_params;
+    let iterations = Arc::new(AtomicUsize::new(0));
+    let deadline = Instant::now() + duration;
+
+    println!("⏱️  Benchmarking {} on {} threads for {:?}...", algo.as_str(), threads, duration);
+    let handles: Vec<_> = (0..threads)
+        .map(|i| {
+            let iterations = iterations.clone();
+            let algo = algo.clone();
+            thread::spawn(move || {
+                let mut source: Box<dyn ObjectSource> = match seed {
+                    Some(seed) => Box::new(SeededSource::new(seed.wrapping_add(i as u64))),
+                    None => Box::new(GeneratedSource),
+                };
+                while Instant::now() < deadline {
+                    if let Some(mining_obj) = source.next_object() {
+                        let _ = p3d_process(mining_obj.obj.as_slice(), algo.as_p3d_algo(), grid as i16, sect as i16, None);
+                        iterations.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().expect("Benchmark thread panicked");
     }
+
+    let iterations = iterations.load(Ordering::Relaxed);
+    println!(
+        "⏱️  Speed: {} it/s ({} iterations)",
+        Style::new().bold().paint(format!("{:.2}", iterations as f64 / duration.as_secs_f64())),
+        iterations,
+    );
 }
 
+/// Mines a fixed object to difficulty 1 like a worker would, checks the result the way
+/// the node verifies it, then round trips the encryption and signature with throwaway keys.
+/// Covers everything but talking to a pool.
+pub(crate) fn selftest(p3d_params: P3dParams) -> anyhow::Result<()> {
+    let P3dParams { algo, sect, grid } = p3d_params;
+    let pre_hash = H256::repeat_byte(0x33);
+    let rot = pre_hash.encode()[0..4].try_into().ok();
+    let mining_obj = SeededSource::new(SELFTEST_SEED)
+        .next_object()
+        .ok_or_else(|| anyhow::anyhow!("No object generated"))?;
+
+    let start = Instant::now();
+    let compute = || p3d_process(mining_obj.obj.as_slice(), algo.as_p3d_algo(), grid as i16, sect as i16, rot);
+    let hashes = compute().map_err(|e| anyhow::anyhow!("Compute error: {:?}", e))?;
+    if hashes.is_empty() {
+        anyhow::bail!("Compute returned no hashes");
+    }
+    check_hashes(&hashes).map_err(|e| anyhow::anyhow!("Malformed {} result: {}", algo.as_str(), e))?;
+    println!("✅ Computed {} in {:?}", algo.as_str(), start.elapsed());
+
+    let obj_hash = H256::from_str(&hashes[0])?;
+    let poscan_hash = DoubleHash { pre_hash, obj_hash }.calc_hash();
+    let comp = Compute { difficulty: U256::one(), pre_hash, poscan_hash };
+    let diff = get_hash_difficulty(&comp.get_work());
+    if diff < comp.difficulty {
+        anyhow::bail!("Proposal difficulty {} below {}", diff, comp.difficulty);
+    }
+    // The node recomputes the object, which only works if compute is deterministic
+    let verify_hashes = compute().map_err(|e| anyhow::anyhow!("Compute error on verification: {:?}", e))?;
+    if verify_hashes.first() != Some(&hashes[0]) {
+        anyhow::bail!("Recomputing the object gave a different hash");
+    }
+    println!("✅ Proposal verified, obj_hash: {:?}, difficulty {}", obj_hash, format_difficulty(&diff));
+
+    let (secret, public) = ecies_ed25519::generate_keypair(&mut rand::rngs::OsRng);
+    let encrypted = ecies_ed25519::encrypt(&public, &mining_obj.obj, &mut rand::rngs::OsRng)
+        .map_err(|e| anyhow::anyhow!("Encryption error: {:?}", e))?;
+    let decrypted = ecies_ed25519::decrypt(&secret, &encrypted)
+        .map_err(|e| anyhow::anyhow!("Decryption error: {:?}", e))?;
+    if decrypted != mining_obj.obj {
+        anyhow::bail!("Decrypted payload differs from the original");
+    }
+    let signer = KeySigner::from_hex(&hex::encode(rand::random::<[u8; 32]>()))?;
+    let sign = signer.sign(&encrypted)?;
+    let public_key = signer.public_key().ok_or_else(|| anyhow::anyhow!("No public key"))?;
+    if !verify_signature(&public_key, &encrypted, &sign) {
+        anyhow::bail!("Signature doesn't verify");
+    }
+    println!("✅ Payload encrypted and signed");
+
+    Ok(())
+}
 
 pub(crate) async fn node_client(ctx: Arc<MiningContext>) {
-    loop {
+    // Found objects wait in out_queue until params arrived and had time to settle
+    while ctx.cur_state.lock().unwrap().is_none() && !ctx.shutdown.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if !ctx.warmup.is_zero() {
+        println!("⏳ Warming up for {:?} before submitting", ctx.warmup);
+        tokio::time::sleep(ctx.warmup).await;
+        println!("🟢 Warmed up, submitting found objects");
+    }
+
+    let mut last_flush = Instant::now();
+    while !ctx.shutdown.load(Ordering::Relaxed) {
+        if ctx.paused.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            continue;
+        }
+        if let Some(drain_interval) = ctx.drain_interval {
+            // Scheduled mode: proposals are held in out_queue and flushed together
+            let queued = ctx.out_queue.lock().unwrap().len();
+            let threshold_reached = ctx.drain_threshold.is_some_and(|threshold| queued >= threshold);
+            if last_flush.elapsed() < drain_interval && !threshold_reached {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+            last_flush = Instant::now();
+            ctx.prune_stale_proposals();
+
+            // Taken one by one so what the rate limit holds back stays in out_queue
+            let batch_size = ctx.out_queue.lock().unwrap().len();
+            for _ in 0..batch_size {
+                if let Some(limiter) = &ctx.submit_limiter {
+                    limiter.acquire().await;
+                }
+                let maybe_prop = ctx.out_queue.lock().unwrap().pop_front();
+                match maybe_prop {
+                    Some(prop) => submit(&ctx, prop).await,
+                    None => break,
+                }
+            }
+            continue;
+        }
+
+        if ctx.out_queue.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            continue;
+        }
+        if let Some(limiter) = &ctx.submit_limiter {
+            limiter.acquire().await;
+        }
         let maybe_prop = {
             let mut lock = ctx.out_queue.lock().unwrap();
             (*lock).pop_front()
         };
         if let Some(prop) = maybe_prop {
-            let res = ctx.push_to_node(prop).await;
-            if let Err(e) = res {
-                println!("🟥 Error: {}", &e);
-            }
+            submit(&ctx, prop).await;
         } else {
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
     }
 }
 
+/// Pushes a proposal in the background once a submit slot is free
+async fn submit(ctx: &Arc<MiningContext>, prop: MiningProposal) {
+    let wait_start = Instant::now();
+    let permit = ctx.submit_slots.clone().acquire_owned().await.expect("Submit slots closed");
+    ctx.submit_wait.record(wait_start.elapsed());
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let _inflight = InflightGuard::new(&ctx.inflight_submissions);
+        let res = ctx.push_to_node(prop).await;
+        ctx.pushed_objects.fetch_add(1, Ordering::Relaxed);
+        drop(permit);
+        if let Err(e) = res {
+            println!("🟥 Error: {}", &e);
+        }
+    });
+}
+
+/// Stops mining and gives queued proposals up to `drain_timeout` to reach the node.
+/// What's left after that is dropped so shutdown time stays bounded.
+pub(crate) async fn shutdown(ctx: Arc<MiningContext>, drain_timeout: Duration) {
+    ctx.shutdown.store(true, Ordering::Relaxed);
+    // Proposals for outdated params would only be rejected, e.g. ones a scheduled drain held
+    ctx.prune_stale_proposals();
+
+    let pushed_before = ctx.pushed_objects.load(Ordering::Relaxed);
+    let pending = ctx.out_queue.lock().unwrap().len() + ctx.inflight_submissions.load(Ordering::Relaxed);
+    println!("🛑 Shutting down, draining {} proposals (timeout {:?})...", pending, drain_timeout);
+
+    let drained = time::timeout(drain_timeout, async {
+        let drain = ctx.drain_proposals();
+        futures::pin_mut!(drain);
+        while let Some((prop, outcome)) = drain.next().await {
+            match outcome {
+                PushOutcome::Failed(e) => println!("🟥 Error pushing obj_hash {:?}: {}", prop.hash, &e),
+                PushOutcome::Rejected(reason) => println!("🟥 obj_hash {:?} rejected: {}", prop.hash, reason),
+                PushOutcome::Unknown => println!("❔ obj_hash {:?} may not have reached the node", prop.hash),
+                PushOutcome::Submitted | PushOutcome::Duplicate => {}
+            }
+        }
+        while ctx.inflight_submissions.load(Ordering::Relaxed) > 0 {
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    }).await.is_ok();
+
+    let dropped: Vec<MiningProposal> = ctx.out_queue.lock().unwrap().drain(..).collect();
+    for prop in dropped.iter() {
+        println!("🟥 Dropping unsent proposal obj_hash: {:?}", prop.hash);
+    }
+    let flushed = ctx.pushed_objects.load(Ordering::Relaxed) - pushed_before;
+    let abandoned = pending.saturating_sub(flushed + dropped.len());
+
+    if !drained {
+        println!("🟥 Drain timeout exceeded");
+    }
+    println!(
+        "🛑 Shutdown: {} flushed, 0 persisted (no persistence), {} dropped, {} abandoned in flight",
+        flushed,
+        dropped.len(),
+        abandoned,
+    );
+}
+
+pub(crate) async fn log_connection_events(ctx: Arc<MiningContext>) {
+    let mut events = ctx.connection_events.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => println!("🔌 {}", event),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+pub(crate) async fn log_params_events(ctx: Arc<MiningContext>) {
+    let mut events = ctx.params_events.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => println!("🎯 {}", event),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
 pub(crate) fn start_timer(ctx: Arc<MiningContext>) {
     let _forever = tokio::spawn(async move {
         let mut interval = time::interval(ASK_MINING_PARAMS_PERIOD);
@@ -183,11 +679,24 @@ pub(crate) fn start_timer(ctx: Arc<MiningContext>) {
         let mut ema_bad_objects_per_second: f64 = 0.0;
         let mut prev_dupe_objects: usize = 0;
         let mut ema_dupe_objects_per_second: f64 = 0.0;
+        let mut prev_submit_wait = HistogramSnapshot::default();
+        let mut prev_encrypt_time = HistogramSnapshot::default();
+        let mut prev_sign_time = HistogramSnapshot::default();
+        let mut prev_pushed: usize = 0;
         // EMA smoothing factor between 0 and 1; higher value means more smoothing
         let alpha: f64 = 0.8;
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = ctx.params_refresh.notified() => {
+                    println!("🔄 Stale rejection, refreshing mining params");
+                    refresh_mining_params(&ctx).await;
+                    // Rejections that came in meanwhile were for the old params too
+                    ctx.refresh_pending.store(false, Ordering::Relaxed);
+                    continue;
+                }
+            }
 
             let current_iterations = ctx.iterations_count.load(Ordering::Relaxed);
             let diff_iterations = current_iterations - prev_iterations;
@@ -207,6 +716,7 @@ pub(crate) fn start_timer(ctx: Arc<MiningContext>) {
             ema_iterations_per_second = alpha * iterations_per_second + (1.0 - alpha) * ema_iterations_per_second;
             ema_bad_objects_per_second = alpha * bad_objects_per_second + (1.0 - alpha) * ema_bad_objects_per_second;
             ema_dupe_objects_per_second = alpha * dupe_objects_per_second + (1.0 - alpha) * ema_dupe_objects_per_second;
+            ctx.hashrate.store(ema_iterations_per_second.to_bits(), Ordering::Relaxed);
 
             println!(
                 "⏱️  Speed: {} it/s, {} bad objects, {} dupe objects",
@@ -215,19 +725,156 @@ pub(crate) fn start_timer(ctx: Arc<MiningContext>) {
                 Style::new().bold().paint(format!("{:.2}%", ema_dupe_objects_per_second / ema_iterations_per_second * 100.0)),
             );
 
+            let current_pushed = ctx.pushed_objects.load(Ordering::Relaxed);
+            let submit_rate = (current_pushed - prev_pushed) as f64 / duration_in_seconds;
+            ctx.submit_rate.store(submit_rate.to_bits(), Ordering::Relaxed);
+            if let Some(limiter) = &ctx.submit_limiter {
+                println!(
+                    "🚰 Submission rate: {}/s, limit {}/s",
+                    Style::new().bold().paint(format!("{:.2}", submit_rate)),
+                    limiter.rate(),
+                );
+            }
+
+            let current_submit_wait = ctx.submit_wait.snapshot();
+            let current_encrypt_time = ctx.encrypt_time.snapshot();
+            let current_sign_time = ctx.sign_time.snapshot();
+            let submit_wait = current_submit_wait.since(&prev_submit_wait);
+            let encrypt_time = current_encrypt_time.since(&prev_encrypt_time);
+            let sign_time = current_sign_time.since(&prev_sign_time);
+
+            println!(
+                "📤 Submissions: {} in flight, {} queued, {} avg wait",
+                Style::new().bold().paint(format!("{}", ctx.inflight_submissions.load(Ordering::Relaxed))),
+                Style::new().bold().paint(format!("{}", ctx.out_queue.lock().unwrap().len())),
+                Style::new().bold().paint(format!("{:.2}ms", submit_wait.mean_ms())),
+            );
+            if encrypt_time.count + sign_time.count > 0 {
+                println!(
+                    "🔐 Crypto: encrypt {} avg (≤{}ms p99), sign {} avg (≤{}ms p99)",
+                    Style::new().bold().paint(format!("{:.3}ms", encrypt_time.mean_ms())),
+                    encrypt_time.quantile_ms(0.99),
+                    Style::new().bold().paint(format!("{:.3}ms", sign_time.mean_ms())),
+                    sign_time.quantile_ms(0.99),
+                );
+            }
+
+            if let Some(budget) = ctx.memory_budget {
+                println!(
+                    "🧠 Memory: {} of {:.1}MB used by queues and caches, {} dropped",
+                    Style::new().bold().paint(format!("{:.1}MB", ctx.memory_usage() as f64 / 1e6)),
+                    budget as f64 / 1e6,
+                    ctx.memory_drops.load(Ordering::Relaxed),
+                );
+            }
+
+            if let Some(max) = ctx.max_pipeline_objects {
+                println!(
+                    "🚦 Pipeline: {} of {} objects in flight",
+                    Style::new().bold().paint(format!("{}", ctx.pipeline_objects())),
+                    max,
+                );
+            }
+
+            println!(
+                "📬 Pool node: {} accepted, {} rejected, {} unknown",
+                Style::new().bold().paint(format!("{}", ctx.accepted_objects.load(Ordering::Relaxed))),
+                Style::new().bold().paint(format!("{}", ctx.rejected_objects.load(Ordering::Relaxed))),
+                Style::new().bold().paint(format!("{}", ctx.unknown_outcomes.load(Ordering::Relaxed))),
+            );
+            println!(
+                "💎 Submitted: {} shares, {} blocks",
+                Style::new().bold().paint(format!("{}", ctx.found_shares.load(Ordering::Relaxed))),
+                Style::new().bold().paint(format!("{}", ctx.found_blocks.load(Ordering::Relaxed))),
+            );
+            let found_difficulty = ctx.found_difficulty.counts();
+            if found_difficulty.iter().any(|&count| count > 0) {
+                let mut line = String::new();
+                for (bound, count) in DIFFICULTY_RATIOS.iter().zip(found_difficulty) {
+                    if bound.is_finite() {
+                        write!(line, " ≤{}x: {}", bound, count).unwrap();
+                    } else {
+                        write!(line, " more: {}", count).unwrap();
+                    }
+                }
+                println!("🎲 Found difficulty over pool difficulty:{}", line);
+            }
+            if ctx.min_difficulty.is_some() {
+                println!(
+                    "🪜 Below min difficulty: {} proposals not submitted",
+                    Style::new().bold().paint(format!("{}", ctx.floor_filtered.load(Ordering::Relaxed))),
+                );
+            }
+            if ctx.paused.load(Ordering::Relaxed) {
+                println!("⏸️  Paused by the pool node");
+            }
+            if ctx.login_required() && !ctx.logged_in() {
+                println!("🔑 Not logged in to the pool node, the next submission retries");
+            }
+            if !ctx.member_registered.load(Ordering::Relaxed) {
+                println!("🟥 member_registered: false, submissions are rejected for unknown member {}", ctx.member_id);
+            }
+
+            println!("👷 Workers: {}", Style::new().bold().paint(format!("{}", ctx.workers.load(Ordering::Relaxed))));
+
+            let worker_restarts = ctx.worker_restarts.load(Ordering::Relaxed);
+            if worker_restarts > 0 {
+                println!("🛠️  Worker restarts: {}", Style::new().bold().paint(format!("{}", worker_restarts)));
+            }
+
+            if let Some(params) = ctx.cur_state.lock().unwrap().as_ref() {
+                println!(
+                    "🎯 Difficulty: pool {}, network {}",
+                    Style::new().bold().paint(format_difficulty(&params.pow_difficulty)),
+                    Style::new().bold().paint(format_difficulty(&params.win_difficulty)),
+                );
+            }
+
             prev_iterations = current_iterations;
             prev_bad_objects = current_bad_objects;
             prev_dupe_objects = current_dupe_objects;
+            prev_submit_wait = current_submit_wait;
+            prev_encrypt_time = current_encrypt_time;
+            prev_sign_time = current_sign_time;
+            prev_pushed = current_pushed;
+            ctx.compact_queues();
+            refresh_mining_params(&ctx).await;
+        }
+    });
+}
 
-            let res = ctx.ask_mining_params().await;
-            if let Err(e) = res {
-                println!("🟥 Ask for mining params error: {}", &e);
+/// Polls the mining params, then does what waits for a node that answers: capabilities
+/// that couldn't be negotiated yet and the lag check
+async fn refresh_mining_params(ctx: &MiningContext) {
+    match ctx.poll_mining_params().await {
+        Ok(_) => {
+            if ctx.capabilities_pending.load(Ordering::Relaxed) {
+                ctx.negotiate_capabilities().await;
             }
+            ctx.check_node_lag().await;
+        }
+        Err(e) => println!("🟥 Ask for mining params error: {}", &e),
+    }
+}
+
+/// Prints the compact status line every `period`, between the detailed ones of `start_timer`
+pub(crate) fn start_status_line(ctx: Arc<MiningContext>, period: Duration) {
+    let _forever = tokio::spawn(async move {
+        let mut interval = time::interval(period);
+        // The first tick is immediate, nothing to show yet
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            println!("{}", ctx.stats_snapshot().status_line());
         }
     });
 }
 
 pub fn create_mining_obj() -> Vec<u8> {
+    create_mining_obj_with(&mut thread_rng())
+}
+
+pub fn create_mining_obj_with<R: Rng>(rng: &mut R) -> Vec<u8> {
     let dents_count = 36;
     let dent_size: f32 = 0.2;
 
@@ -238,14 +885,13 @@ pub fn create_mining_obj() -> Vec<u8> {
         .map(|v: [f32; 3]| Vector3::new(v[0], v[1], v[2]))
         .collect();
 
-    let mut rng = thread_rng();
     let vertices_count = vertices.len();
 
 	
     // Generate all indices
     let mut indices: Vec<usize> = (0..vertices_count).collect();
     // Shuffle all indices
-    indices.shuffle(&mut rng);
+    indices.shuffle(rng);
     // Take the first dents_count indices
     let random_indices: Vec<usize> = indices.into_iter().take(dents_count).collect();
 
@@ -293,3 +939,486 @@ pub fn create_mining_obj() -> Vec<u8> {
 
     obj_data.into_bytes()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use jsonrpsee::server::RpcModule;
+
+    use super::*;
+    use crate::rpc::MiningOptions;
+    use crate::rpc::tests::{
+        body, mock_node, params_node, params_response, plaintext, recording_node, test_context, test_params, test_proposal,
+        wait_until,
+    };
+
+    /// Submissions in flight now, the most at once and the total
+    #[derive(Default)]
+    struct Concurrency {
+        current: usize,
+        max: usize,
+        total: usize,
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn max_inflight_caps_concurrent_pushes() {
+        let counts = Arc::new(Mutex::new(Concurrency::default()));
+        let mut module = RpcModule::new(counts.clone());
+        module
+            .register_async_method("poscan_pushMiningObjectToPool", |_, counts| async move {
+                {
+                    let mut counts = counts.lock().unwrap();
+                    counts.current += 1;
+                    counts.max = counts.max.max(counts.current);
+                }
+                // A slow node, so pushes pile up behind the submit slots
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                let mut counts = counts.lock().unwrap();
+                counts.current -= 1;
+                counts.total += 1;
+                Ok(serde_json::Value::Null)
+            })
+            .unwrap();
+        let (url, _node) = mock_node(module).await;
+
+        let ctx = Arc::new(test_context(&url, MiningOptions { max_inflight: 2, ..plaintext() }));
+        *ctx.cur_state.lock().unwrap() = Some(test_params());
+        for hash in 1..=6 {
+            ctx.push_to_queue(test_proposal(hash));
+        }
+        tokio::spawn(node_client(ctx.clone()));
+
+        wait_until(|| counts.lock().unwrap().total == 6).await;
+        ctx.shutdown.store(true, Ordering::Relaxed);
+        assert_eq!(counts.lock().unwrap().max, 2);
+    }
+
+    /// Accepts connections and never answers, like a node that hung
+    fn blackhole_node() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let mut held = Vec::new();
+            for stream in listener.incoming() {
+                held.push(stream);
+            }
+        });
+        url
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_is_bounded_by_the_drain_timeout() {
+        let ctx = Arc::new(test_context(&blackhole_node(), plaintext()));
+        for hash in 1..=3 {
+            ctx.push_to_queue(test_proposal(hash));
+        }
+
+        let start = Instant::now();
+        let stopping = tokio::spawn(shutdown(ctx.clone(), Duration::from_millis(500)));
+        wait_until(|| ctx.inflight_submissions.load(Ordering::Relaxed) == 1).await;
+        stopping.await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(ctx.out_queue.lock().unwrap().is_empty());
+        assert_eq!(ctx.pushed_objects.load(Ordering::Relaxed), 0);
+        // Cut off mid push, it's no longer in flight
+        assert_eq!(ctx.inflight_submissions.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn aborted_pushes_leave_the_pipeline() {
+        let url = blackhole_node();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let ctx = runtime.block_on(async {
+            let ctx = Arc::new(test_context(&url, MiningOptions { max_pipeline_objects: Some(1), ..plaintext() }));
+            submit(&ctx, test_proposal(1)).await;
+            wait_until(|| ctx.pipeline_full()).await;
+            ctx
+        });
+        // Drops the push mid flight
+        drop(runtime);
+        assert_eq!(ctx.inflight_submissions.load(Ordering::Relaxed), 0);
+        assert!(!ctx.pipeline_full());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_drops_stale_proposals_instead_of_flushing_them() {
+        let (url, _node, received) = recording_node().await;
+        let ctx = Arc::new(test_context(&url, plaintext()));
+        *ctx.cur_state.lock().unwrap() = Some(test_params());
+        ctx.push_to_queue(test_proposal(1));
+        let stale = test_proposal(2);
+        ctx.push_to_queue(MiningProposal { params: MiningParams { pre_hash: H256::repeat_byte(9), ..stale.params.clone() }, ..stale });
+        ctx.push_to_queue(test_proposal(3));
+
+        shutdown(ctx.clone(), Duration::from_secs(5)).await;
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert_eq!(ctx.stale_proposals.load(Ordering::Relaxed), 1);
+        assert_eq!(ctx.pushed_objects.load(Ordering::Relaxed), 2);
+    }
+
+    /// Hands out a fixed list of objects, then nothing
+    struct VecSource(VecDeque<MiningObj>);
+
+    impl ObjectSource for VecSource {
+        fn next_object(&mut self) -> Option<MiningObj> {
+            self.0.pop_front()
+        }
+    }
+
+    fn sphere(obj_id: u64) -> MiningObj {
+        MiningObj {
+            obj_id,
+            obj: create_mining_obj(),
+            source: crate::rpc::ObjSource::Text,
+            deadline: None,
+            difficulty: None,
+        }
+    }
+
+    /// Mines `objects` on a worker thread until `done`, then stops it
+    fn mine(ctx: &Arc<MiningContext>, objects: Vec<MiningObj>, done: impl Fn() -> bool) {
+        let mut source = VecSource(objects.into());
+        let mining = {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let state = WorkerState::new(0, &ctx);
+                worker(&ctx, &mut source, &state);
+            })
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !done() {
+            assert!(Instant::now() < deadline, "Timed out mining the objects");
+            thread::sleep(Duration::from_millis(20));
+        }
+        ctx.shutdown.store(true, Ordering::Relaxed);
+        mining.join().unwrap();
+    }
+
+    #[test]
+    fn worker_mines_what_the_source_hands_out() {
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", plaintext()));
+        // Every hash is a share at difficulty 1
+        *ctx.cur_state.lock().unwrap() = Some(MiningParams { pow_difficulty: U256::one(), ..test_params() });
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.out_queue.lock().unwrap().len() == 3);
+
+        let mut obj_ids: Vec<u64> = ctx.out_queue.lock().unwrap().iter().map(|prop| prop.obj_id).collect();
+        obj_ids.sort();
+        assert_eq!(obj_ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn found_difficulties_are_recorded_against_the_pool_difficulty() {
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", plaintext()));
+        let params = MiningParams { pow_difficulty: U256::one(), win_difficulty: U256::MAX, ..test_params() };
+        *ctx.cur_state.lock().unwrap() = Some(params);
+        mine(&ctx, (1..=4).map(sphere).collect(), || ctx.out_queue.lock().unwrap().len() == 4);
+
+        // At pool difficulty 1 the ratio is the difficulty found
+        let mut expected = [0; DIFFICULTY_RATIOS.len()];
+        for prop in ctx.out_queue.lock().unwrap().iter() {
+            let ratio = difficulty_to_f64(&prop.difficulty);
+            expected[DIFFICULTY_RATIOS.iter().position(|&b| ratio <= b).unwrap()] += 1;
+        }
+        assert_eq!(ctx.found_difficulty.counts(), expected);
+    }
+
+    #[test]
+    fn formats_known_difficulties() {
+        assert_eq!(format_difficulty(&U256::zero()), "0.00 (0.0 bits)");
+        assert_eq!(format_difficulty(&U256::from(1)), "1.00 (0.0 bits)");
+        assert_eq!(format_difficulty(&U256::from(1000)), "1.00K (10.0 bits)");
+        assert_eq!(format_difficulty(&U256::from(1_500_000)), "1.50M (20.5 bits)");
+        assert_eq!(format_difficulty(&(U256::one() << 64)), "18.45E (64.0 bits)");
+    }
+
+    #[test]
+    fn target_hashes_give_their_difficulty() {
+        // Two leading zero bytes are 16 bits of work
+        let mut target = [0xffu8; 32];
+        target[..2].copy_from_slice(&[0, 0]);
+        let difficulty = get_hash_difficulty(&H256::from(target));
+        assert_eq!(difficulty, U256::from(65536));
+        assert_eq!(format_difficulty(&difficulty), "65.54K (16.0 bits)");
+
+        assert_eq!(get_hash_difficulty(&H256::repeat_byte(0xff)), U256::one());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watchdog_restarts_a_hung_worker() {
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", plaintext()));
+        // Holding the params lock hangs the worker the way a stuck compute would
+        let (locked, hung) = std::sync::mpsc::channel();
+        let holder = {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let _params = ctx.cur_state.lock().unwrap();
+                locked.send(()).unwrap();
+                while ctx.worker_restarts.load(Ordering::Relaxed) == 0 {
+                    thread::sleep(Duration::from_millis(20));
+                }
+            })
+        };
+        hung.recv().unwrap();
+
+        start_workers(ctx.clone(), 1, None, Duration::from_millis(500));
+        wait_until(|| ctx.worker_restarts.load(Ordering::Relaxed) > 0).await;
+        holder.join().unwrap();
+        ctx.shutdown.store(true, Ordering::Relaxed);
+        assert_eq!(ctx.workers.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scheduled_drain_holds_proposals_until_the_interval() {
+        let (url, _node, received) = recording_node().await;
+        let options = MiningOptions { drain_interval: Some(Duration::from_secs(1)), ..plaintext() };
+        let ctx = Arc::new(test_context(&url, options));
+        *ctx.cur_state.lock().unwrap() = Some(test_params());
+        for hash in 1..=3 {
+            ctx.push_to_queue(test_proposal(hash));
+        }
+        tokio::spawn(node_client(ctx.clone()));
+
+        time::sleep(Duration::from_millis(500)).await;
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(ctx.out_queue.lock().unwrap().len(), 3);
+
+        wait_until(|| received.lock().unwrap().len() == 3).await;
+        // Found after the flush, it waits for the next one
+        ctx.push_to_queue(test_proposal(4));
+        time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(received.lock().unwrap().len(), 3);
+        assert_eq!(ctx.out_queue.lock().unwrap().len(), 1);
+        ctx.shutdown.store(true, Ordering::Relaxed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stale_refreshes_poll_like_the_timer() {
+        let (url, node, _) = params_node(params_response("")).await;
+        let ctx = Arc::new(test_context(&url, plaintext()));
+        start_timer(ctx.clone());
+        wait_until(|| ctx.connected.load(Ordering::Relaxed)).await;
+
+        // Only a poll notices the node went away
+        node.stop().unwrap();
+        node.stopped().await;
+        ctx.refresh_pending.store(true, Ordering::Relaxed);
+        ctx.params_refresh.notify_one();
+        wait_until(|| !ctx.refresh_pending.load(Ordering::Relaxed)).await;
+        assert!(!ctx.connected.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pause_holds_mining_and_submissions_until_resumed() {
+        let (submit_url, _submit_node, received) = recording_node().await;
+        let mut response = params_response("");
+        // Every hash is a share at pool difficulty 1
+        response[3] = serde_json::json!("1");
+        response.as_array_mut().unwrap().extend([serde_json::Value::Null, serde_json::json!("pause")]);
+        let (url, _node, response) = params_node(response).await;
+        let ctx = Arc::new(test_context(&url, MiningOptions { submit_addr: Some(submit_url), ..plaintext() }));
+        ctx.ask_mining_params().await.unwrap();
+        assert!(ctx.paused.load(Ordering::Relaxed));
+
+        ctx.push_to_queue(test_proposal(1));
+        tokio::spawn(node_client(ctx.clone()));
+        let mining = {
+            let ctx = ctx.clone();
+            let mut source = VecSource((1..=3).map(sphere).collect());
+            thread::spawn(move || worker(&ctx, &mut source, &WorkerState::new(0, &ctx)))
+        };
+
+        time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(ctx.iterations_count.load(Ordering::Relaxed), 0);
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(ctx.out_queue.lock().unwrap().len(), 1);
+
+        response.lock().unwrap()[6] = serde_json::Value::Null;
+        ctx.ask_mining_params().await.unwrap();
+        assert!(!ctx.paused.load(Ordering::Relaxed));
+        // The held proposal and the three mined after resuming
+        wait_until(|| received.lock().unwrap().len() == 4).await;
+        assert_eq!(ctx.iterations_count.load(Ordering::Relaxed), 3);
+        ctx.shutdown.store(true, Ordering::Relaxed);
+        mining.join().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shares_are_held_back_with_blocks_only() {
+        let (url, _node, received) = recording_node().await;
+        let params = MiningParams { pow_difficulty: U256::one(), win_difficulty: U256::MAX, ..test_params() };
+
+        let ctx = Arc::new(test_context(&url, plaintext()));
+        *ctx.cur_state.lock().unwrap() = Some(params.clone());
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.out_queue.lock().unwrap().len() == 3);
+        // Stopped mining, the drain pushes what's queued
+        ctx.drain_proposals().collect::<Vec<_>>().await;
+        assert_eq!(received.lock().unwrap().len(), 3);
+        assert_eq!(ctx.found_shares.load(Ordering::Relaxed), 3);
+        assert_eq!(ctx.found_blocks.load(Ordering::Relaxed), 0);
+
+        let ctx = Arc::new(test_context(&url, MiningOptions { blocks_only: true, ..plaintext() }));
+        *ctx.cur_state.lock().unwrap() = Some(params);
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.iterations_count.load(Ordering::Relaxed) == 3);
+        assert!(ctx.out_queue.lock().unwrap().is_empty());
+        assert_eq!(ctx.found_difficulty.counts().iter().sum::<u64>(), 0);
+
+        // Blocks still go out
+        ctx.push_to_queue(MiningProposal { is_block: true, ..test_proposal(4) });
+        ctx.drain_proposals().collect::<Vec<_>>().await;
+        assert_eq!(received.lock().unwrap().len(), 4);
+        assert_eq!(ctx.found_blocks.load(Ordering::Relaxed), 1);
+        assert_eq!(ctx.found_shares.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn nothing_is_submitted_before_the_params_settled() {
+        let (url, _node, received) = recording_node().await;
+        let ctx = Arc::new(test_context(&url, MiningOptions { warmup: Duration::from_millis(500), ..plaintext() }));
+        ctx.push_to_queue(test_proposal(1));
+        tokio::spawn(node_client(ctx.clone()));
+
+        time::sleep(Duration::from_millis(300)).await;
+        assert!(received.lock().unwrap().is_empty());
+
+        *ctx.cur_state.lock().unwrap() = Some(test_params());
+        let applied = Instant::now();
+        time::sleep(Duration::from_millis(300)).await;
+        assert!(received.lock().unwrap().is_empty());
+
+        wait_until(|| received.lock().unwrap().len() == 1).await;
+        assert!(applied.elapsed() >= Duration::from_millis(500));
+        ctx.shutdown.store(true, Ordering::Relaxed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn object_difficulty_overrides_the_pool_difficulty() {
+        let (url, _node, received) = recording_node().await;
+        let ctx = Arc::new(test_context(&url, plaintext()));
+        // Nothing would be a share at the pool difficulty
+        let params = MiningParams { pow_difficulty: U256::MAX, win_difficulty: U256::MAX, ..test_params() };
+        *ctx.cur_state.lock().unwrap() = Some(params);
+        let objects = (1..=2).map(|obj_id| MiningObj { difficulty: Some(U256::one()), ..sphere(obj_id) }).collect();
+        mine(&ctx, objects, || ctx.out_queue.lock().unwrap().len() == 2);
+        assert!(ctx.out_queue.lock().unwrap().iter().all(|prop| prop.params.pow_difficulty == U256::one()));
+
+        ctx.drain_proposals().collect::<Vec<_>>().await;
+        for params in received.lock().unwrap().iter() {
+            let payload: serde_json::Value = serde_json::from_slice(&body(params)).unwrap();
+            assert_eq!(payload["dfclty"], serde_json::to_value(U256::one()).unwrap());
+        }
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn proposals_below_the_floor_are_not_queued() {
+        let options = MiningOptions { min_difficulty: Some(U256::MAX), ..plaintext() };
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", options));
+        // Never above the network difficulty
+        assert_eq!(ctx.difficulty_floor(U256::from(1000)), Some(U256::from(1000)));
+
+        let params = MiningParams { pow_difficulty: U256::one(), win_difficulty: U256::MAX, ..test_params() };
+        *ctx.cur_state.lock().unwrap() = Some(params);
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.floor_filtered.load(Ordering::Relaxed) == 3);
+        assert!(ctx.out_queue.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn proposals_above_the_threshold_are_double_checked() {
+        let options = MiningOptions { double_check_difficulty: Some(U256::one()), ..plaintext() };
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", options));
+        let params = MiningParams { pow_difficulty: U256::one(), win_difficulty: U256::MAX, ..test_params() };
+        *ctx.cur_state.lock().unwrap() = Some(params.clone());
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.out_queue.lock().unwrap().len() == 3);
+
+        assert_eq!(ctx.double_checks.load(Ordering::Relaxed), 3);
+        assert_eq!(ctx.double_check_failures.load(Ordering::Relaxed), 0);
+        for prop in ctx.out_queue.lock().unwrap().iter() {
+            assert_eq!(prop.difficulty, work_difficulty(params.pre_hash, prop.hash, U256::one()));
+        }
+    }
+
+    #[test]
+    fn proposals_below_the_threshold_skip_the_double_check() {
+        let options = MiningOptions { double_check_difficulty: Some(U256::MAX), ..plaintext() };
+        let ctx = Arc::new(test_context("http://127.0.0.1:9933", options));
+        let params = MiningParams { pow_difficulty: U256::one(), win_difficulty: U256::MAX, ..test_params() };
+        *ctx.cur_state.lock().unwrap() = Some(params);
+        mine(&ctx, (1..=3).map(sphere).collect(), || ctx.out_queue.lock().unwrap().len() == 3);
+
+        assert_eq!(ctx.double_checks.load(Ordering::Relaxed), 0);
+        assert_eq!(ctx.double_check_failures.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn hashes_of_the_wrong_shape_are_rejected() {
+        let hash = "ab".repeat(32);
+        assert!(check_hashes(&[hash.clone(), hash.to_uppercase()]).is_ok());
+        assert!(check_hashes(&[]).is_ok());
+
+        for bad in ["ab".repeat(31), "ab".repeat(33), format!("0x{}", &hash[2..]), "zz".repeat(32)] {
+            let e = check_hashes(&[hash.clone(), bad.clone()]).unwrap_err();
+            assert_eq!(e, format!("unexpected hash {:?}", bad));
+        }
+    }
+
+    #[test]
+    fn compute_errors_are_retried_as_configured() {
+        // Fails `failures` times, then gives a hash
+        let flaky = |failures: usize, calls: &mut usize| {
+            *calls += 1;
+            if *calls <= failures { Err("injected") } else { Ok(vec!["ab".repeat(32)]) }
+        };
+        let algo = AlgoType::Grid2dV3_1;
+
+        let ctx = test_context("http://127.0.0.1:9933", MiningOptions { compute_retries: 2, ..plaintext() });
+        let state = WorkerState::new(0, &ctx);
+        let mut calls = 0;
+        assert!(compute_with_retries(&ctx, &state, 1, &algo, || flaky(2, &mut calls)).is_ok());
+        assert_eq!(calls, 3);
+        assert_eq!(ctx.compute_errors[algo.index()].load(Ordering::Relaxed), 2);
+
+        let mut calls = 0;
+        assert_eq!(compute_with_retries(&ctx, &state, 1, &algo, || flaky(5, &mut calls)), Err("injected"));
+        assert_eq!(calls, 3);
+
+        // Dropped at once without retries
+        let ctx = test_context("http://127.0.0.1:9933", plaintext());
+        let state = WorkerState::new(0, &ctx);
+        let mut calls = 0;
+        assert!(compute_with_retries(&ctx, &state, 1, &algo, || flaky(1, &mut calls)).is_err());
+        assert_eq!(calls, 1);
+        assert_eq!(ctx.compute_errors[algo.index()].load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn autoscaler_tracks_the_queue_within_bounds() {
+        let mut autoscaler = Autoscaler::new(1, 3);
+        let mut workers = 1;
+        let mut run = |autoscaler: &mut Autoscaler, depth: usize, ticks: usize| {
+            for _ in 0..ticks {
+                workers = autoscaler.sample(depth, workers);
+            }
+            workers
+        };
+
+        // A backlog adds a worker every SCALE_UP_SAMPLES ticks, up to the max
+        assert_eq!(run(&mut autoscaler, 10, SCALE_UP_SAMPLES - 1), 1);
+        assert_eq!(run(&mut autoscaler, 10, 1), 2);
+        assert_eq!(run(&mut autoscaler, 10, SCALE_UP_SAMPLES), 3);
+        assert_eq!(run(&mut autoscaler, 10, 5 * SCALE_UP_SAMPLES), 3);
+        // A queue the workers keep up with changes nothing
+        assert_eq!(run(&mut autoscaler, 2, 2 * SCALE_DOWN_SAMPLES), 3);
+
+        // Idling removes one every SCALE_DOWN_SAMPLES ticks, down to the min
+        assert_eq!(run(&mut autoscaler, 0, SCALE_DOWN_SAMPLES - 1), 3);
+        assert_eq!(run(&mut autoscaler, 0, 1), 2);
+        assert_eq!(run(&mut autoscaler, 0, SCALE_DOWN_SAMPLES), 1);
+        assert_eq!(run(&mut autoscaler, 0, 5 * SCALE_DOWN_SAMPLES), 1);
+    }
+
+    #[test]
+    fn selftest_passes() {
+        selftest(P3dParams::new("grid2d_v3.1").unwrap()).unwrap();
+    }
+}